@@ -1,5 +1,5 @@
 import React, { useState, useEffect, useRef, useCallback } from 'react';
-import { Palette, Waves, Beaker, RefreshCcw, Paintbrush, Image, Maximize, Download } from 'lucide-react';
+import { Palette, Waves, Beaker, RefreshCcw, Paintbrush, Image, Maximize, Download, Undo2, Redo2 } from 'lucide-react';
 
 const DISPLAY_SCALE = 2.2;
 
@@ -18,18 +18,18 @@ const RATIO_PRESETS = [
 
 // 프리셋 색상 팔레트
 const COLOR_PRESETS = [
-    { name: '울트라마린', color: '#1e3a8a' },
-    { name: '세룰리안', color: '#0284c7' },
-    { name: '비리디안', color: '#047857' },
-    { name: '알리자린', color: '#be123c' },
-    { name: '버밀리온', color: '#dc2626' },
-    { name: '감보지', color: '#f59e0b' },
-    { name: '번트시에나', color: '#92400e' },
-    { name: '페인즈 그레이', color: '#374151' },
-    { name: '인디고', color: '#312e81' },
-    { name: '옐로 오커', color: '#a16207' },
-    { name: '사프 그린', color: '#65a30d' },
-    { name: '로즈 마더', color: '#e11d48' },
+    { name: '울트라마린', color: '#1e3a8a', pigment: 'ultramarine' },
+    { name: '세룰리안', color: '#0284c7', pigment: 'cerulean' },
+    { name: '비리디안', color: '#047857', pigment: 'viridian' },
+    { name: '알리자린', color: '#be123c', pigment: 'alizarin' },
+    { name: '버밀리온', color: '#dc2626', pigment: 'vermilion' },
+    { name: '감보지', color: '#f59e0b', pigment: 'gamboge' },
+    { name: '번트시에나', color: '#92400e', pigment: 'burnt_sienna' },
+    { name: '페인즈 그레이', color: '#374151', pigment: 'paynes_grey' },
+    { name: '인디고', color: '#312e81', pigment: 'indigo' },
+    { name: '옐로 오커', color: '#a16207', pigment: 'yellow_ochre' },
+    { name: '사프 그린', color: '#65a30d', pigment: 'sap_green' },
+    { name: '로즈 마더', color: '#e11d48', pigment: 'rose_madder' },
 ];
 
 const BRUSH_MODES = [
@@ -39,8 +39,19 @@ const BRUSH_MODES = [
     { key: 'blend', label: '블렌딩 붓' },
     { key: 'silhouette_blend', label: '실루엣 블렌딩' },
     { key: 'water', label: '물 번짐 붓' },
+    { key: 'loaded', label: '머금은 붓' },
 ];
 
+// set_brush_tip 번호 순서 (5는 불러온 붓끝 이미지)
+const BRUSH_TIPS = [
+    { key: 0, label: '둥근 붓' },
+    { key: 1, label: '납작붓' },
+    { key: 2, label: '필버트' },
+    { key: 3, label: '부채붓' },
+    { key: 4, label: '리거' },
+];
+const CUSTOM_TIP = 5;
+
 function hexToRgb(hex) {
     const r = parseInt(hex.slice(1, 3), 16) / 255;
     const g = parseInt(hex.slice(3, 5), 16) / 255;
@@ -48,6 +59,28 @@ function hexToRgb(hex) {
     return { r, g, b };
 }
 
+// 프리셋 색상은 엔진의 내장 안료를, 직접 고른 색상은 RGB로 만든 투명 안료를 사용
+function pigmentIdFor(engine, hex) {
+    const preset = COLOR_PRESETS.find((p) => p.color === hex);
+    if (preset) {
+        const id = engine.find_pigment(preset.pigment);
+        if (id >= 0) return id;
+    }
+    // 매 포인터 이동마다 불리므로 이미 만든 안료는 다시 등록하지 않는다 (등록은 붓 기록에 남는다)
+    const name = `custom_${hex}`;
+    const id = engine.find_pigment(name);
+    if (id >= 0) return id;
+    const { r, g, b } = hexToRgb(hex);
+    return engine.register_pigment_rgb(name, r, g, b, 0.05, 0.4, 0.3, 0.1);
+}
+
+function inPalette(engine, id) {
+    for (let slot = 0; slot < engine.palette_len(); slot++) {
+        if (engine.palette_pigment(slot) === id) return true;
+    }
+    return false;
+}
+
 function ControlSlider({ label, value, min, max, step, onChange }) {
     const display = typeof value === 'number' && !Number.isInteger(value)
         ? value.toFixed(3) : value;
@@ -69,6 +102,8 @@ export default function App() {
     const cursorCanvasRef = useRef(null);
     const engineRef = useRef(null);
     const wasmModuleRef = useRef(null);
+    const wasmMemoryRef = useRef(null);
+    const frameCanvasRef = useRef(null);
 
     const [loading, setLoading] = useState(true);
     const [error, setError] = useState(null);
@@ -79,6 +114,9 @@ export default function App() {
     const [fadeStrength, setFadeStrength] = useState(0.35);
     const [blendStrength, setBlendStrength] = useState(0.45);
     const [waterFlow, setWaterFlow] = useState(1.0);
+    // 머금은 붓에 남은 물/안료 (획을 뗄 때 갱신)
+    const [brushLoad, setBrushLoad] = useState({ water: 0, pigment: 0 });
+    const [paletteFull, setPaletteFull] = useState(false);
     const [physics, setPhysics] = useState({
         dt: 0.15, evaporation: 0.002, viscosity: 0.05,
         pressure: 5.0, iterations: 10,
@@ -86,6 +124,9 @@ export default function App() {
     const [pigmentProps, setPigmentProps] = useState({ adhesion: 0.05, granularity: 0.8 });
     const [isSimulating, setIsSimulating] = useState(true);
     const [showTexture, setShowTexture] = useState(true);
+    const [drybrush, setDrybrush] = useState(false);
+    const [brushTip, setBrushTip] = useState(0);
+    const [debugView, setDebugView] = useState(0);
     const [silhouetteStrength, setSilhouetteStrength] = useState(0.85);
     const [edgeBleedStrength, setEdgeBleedStrength] = useState(0.35);
     const [canvasWidth, setCanvasWidth] = useState(420);
@@ -99,6 +140,8 @@ export default function App() {
     const lastPosRef = useRef(null);
     const lastTimeRef = useRef(null);
     const velocityRef = useRef(0);
+    // 직전 펜 표본 (압력, 기울기, 회전). 마우스/터치면 null
+    const penRef = useRef(null);
     const dynamicSizeRef = useRef(8);
 
     // WASM 초기화 (width, height 있으면 재생성)
@@ -107,8 +150,9 @@ export default function App() {
             let wasm = wasmModuleRef.current;
             if (!wasm) {
                 wasm = await import('../wasm-pkg/watercolor_engine.js');
-                await wasm.default();
+                const exports = await wasm.default();
                 wasmModuleRef.current = wasm;
+                wasmMemoryRef.current = exports.memory;
             }
             const engine = new wasm.WatercolorEngine(w, h);
             engineRef.current = engine;
@@ -190,6 +234,27 @@ export default function App() {
         loadPaperTexture(url);
     }, [loadPaperTexture]);
 
+    // 붓끝 이미지 로드 (어두운 곳이 붓털, 가로가 획 진행 방향)
+    const handleTipUpload = useCallback((e) => {
+        const file = e.target.files[0];
+        e.target.value = '';
+        const engine = engineRef.current;
+        if (!file || !engine) return;
+        const url = URL.createObjectURL(file);
+        const img = new window.Image();
+        img.onload = () => {
+            const tmpC = document.createElement('canvas');
+            tmpC.width = img.width; tmpC.height = img.height;
+            const ctx = tmpC.getContext('2d');
+            ctx.drawImage(img, 0, 0);
+            const imageData = ctx.getImageData(0, 0, img.width, img.height);
+            engine.load_brush_tip(imageData.data, img.width, img.height);
+            setBrushTip(engine.brush_tip());
+            URL.revokeObjectURL(url);
+        };
+        img.src = url;
+    }, []);
+
     // 파라미터 동기화
     useEffect(() => {
         const e = engineRef.current;
@@ -209,6 +274,24 @@ export default function App() {
         e.set_show_texture(showTexture);
     }, [showTexture]);
 
+    useEffect(() => {
+        const e = engineRef.current;
+        if (!e) return;
+        e.set_drybrush(drybrush);
+    }, [drybrush]);
+
+    useEffect(() => {
+        const e = engineRef.current;
+        if (!e || brushTip === CUSTOM_TIP) return;
+        e.set_brush_tip(brushTip);
+    }, [brushTip]);
+
+    useEffect(() => {
+        const e = engineRef.current;
+        if (!e) return;
+        e.set_debug_view(debugView);
+    }, [debugView]);
+
     useEffect(() => {
         const e = engineRef.current;
         if (!e || !e.set_silhouette_controls) return;
@@ -225,12 +308,22 @@ export default function App() {
         const ctx = canvas.getContext('2d');
         const w = canvasWidth;
         const h = canvasHeight;
-        const pixelArray = engine.render();
-        const pixelData = new Uint8ClampedArray(pixelArray.buffer || pixelArray);
-        const imageData = new ImageData(pixelData, w, h);
-        const tmp = document.createElement('canvas');
-        tmp.width = w; tmp.height = h;
-        tmp.getContext('2d').putImageData(imageData, 0, 0);
+        // 엔진 버퍼를 복사 없이 보고, 다시 칠해진 영역만 화면 밖 캔버스에 올린다
+        engine.render_frame();
+        const [dx, dy, dw, dh] = engine.take_dirty_rect();
+        let tmp = frameCanvasRef.current;
+        if (!tmp || tmp.width !== w || tmp.height !== h) {
+            tmp = document.createElement('canvas');
+            tmp.width = w; tmp.height = h;
+            frameCanvasRef.current = tmp;
+        }
+        if (dw > 0 && dh > 0) {
+            // 메모리가 커지면 buffer가 바뀌므로 뷰는 매번 새로 만든다 (픽셀은 복사되지 않음)
+            const pixelData = new Uint8ClampedArray(
+                wasmMemoryRef.current.buffer, engine.pixels_ptr(), engine.pixels_len());
+            const imageData = new ImageData(pixelData, w, h);
+            tmp.getContext('2d').putImageData(imageData, 0, 0, dx, dy, dw, dh);
+        }
         ctx.clearRect(0, 0, canvas.width, canvas.height);
         ctx.imageSmoothingEnabled = true;
         ctx.imageSmoothingQuality = 'high';
@@ -308,27 +401,44 @@ export default function App() {
         const scaleY = canvasHeight / rect.height;
         const x = Math.floor((e.clientX - rect.left) * scaleX);
         const y = Math.floor((e.clientY - rect.top) * scaleY);
-        const { r, g, b } = hexToRgb(activeColor);
+        const pigment = pigmentIdFor(engine, activeColor);
         const now = performance.now();
         const { velocity, dynSize } = calcVelocityAndSize(x, y, now);
 
-        if (brushMode === 'paint') {
+        // 펜이면 속도 대신 압력이 붓 크기와 물감 양을, 기울기가 붓 자국 모양을 정한다
+        const pen = e.pointerType === 'pen'
+            ? { pressure: e.pressure, tiltX: e.tiltX || 0, tiltY: e.tiltY || 0, twist: e.twist || 0 }
+            : null;
+
+        if (brushMode === 'paint' && pen) {
+            const last = penRef.current ?? pen;
+            if (isFirst || !lastPosRef.current) {
+                engine.apply_pen_brush(x, y, brush.size, brush.water, brush.pigment, pigment,
+                    pen.pressure, pen.tiltX, pen.tiltY, pen.twist);
+            } else {
+                engine.apply_pen_stroke(
+                    lastPosRef.current.x, lastPosRef.current.y,
+                    x, y, brush.size, brush.water, brush.pigment, pigment,
+                    last.pressure, last.tiltX, last.tiltY, last.twist,
+                    pen.pressure, pen.tiltX, pen.tiltY, pen.twist);
+            }
+        } else if (brushMode === 'paint') {
             if (isFirst || !lastPosRef.current) {
-                engine.apply_brush(x, y, dynSize, brush.water, brush.pigment, r, g, b, 0.0, 1.0);
+                engine.apply_brush(x, y, dynSize, brush.water, brush.pigment, pigment, 0.0, 1.0);
             } else {
                 engine.apply_brush_stroke(
                     lastPosRef.current.x, lastPosRef.current.y,
-                    x, y, dynSize, brush.water, brush.pigment, r, g, b, velocity);
+                    x, y, dynSize, brush.water, brush.pigment, pigment, velocity);
             }
         } else if (brushMode === 'background') {
             if (isFirst || !lastPosRef.current) {
                 engine.apply_background_brush_stroke(
                     x, y,
-                    x, y, dynSize, backgroundBrush.water, backgroundBrush.pigment, r, g, b);
+                    x, y, dynSize, backgroundBrush.water, backgroundBrush.pigment, pigment);
             } else {
                 engine.apply_background_brush_stroke(
                     lastPosRef.current.x, lastPosRef.current.y,
-                    x, y, dynSize, backgroundBrush.water, backgroundBrush.pigment, r, g, b);
+                    x, y, dynSize, backgroundBrush.water, backgroundBrush.pigment, pigment);
             }
         } else if (brushMode === 'fade') {
             if (isFirst || !lastPosRef.current) {
@@ -360,6 +470,14 @@ export default function App() {
                     lastPosRef.current.x, lastPosRef.current.y,
                     x, y, dynSize, blendStrength, velocity);
             }
+        } else if (brushMode === 'loaded') {
+            if (isFirst || !lastPosRef.current) {
+                engine.apply_loaded_brush(x, y, dynSize, 0.0, 1.0);
+            } else {
+                engine.apply_loaded_brush_stroke(
+                    lastPosRef.current.x, lastPosRef.current.y,
+                    x, y, dynSize, velocity);
+            }
         } else if (brushMode === 'water') {
             if (isFirst || !lastPosRef.current) {
                 engine.apply_water_brush_stroke(
@@ -373,25 +491,57 @@ export default function App() {
         }
         lastPosRef.current = { x, y };
         lastTimeRef.current = now;
+        penRef.current = pen;
         drawCursor(e.clientX, e.clientY);
     }, [brush, backgroundBrush, brushMode, fadeStrength, blendStrength, waterFlow, activeColor, canvasWidth, canvasHeight, calcVelocityAndSize, drawCursor]);
 
-    const handleMouseDown = useCallback((e) => {
+    const handlePointerDown = useCallback((e) => {
         velocityRef.current = 0;
         dynamicSizeRef.current = brushMode === 'background' ? backgroundBrush.size : brush.size;
+        engineRef.current?.begin_stroke();
         handleInteraction(e, true);
+        // 팔레트 슬롯이 모두 차 있으면 새 안료는 칠해지지 않고 물만 남는다
+        const engine = engineRef.current;
+        if (engine && (brushMode === 'paint' || brushMode === 'background')) {
+            setPaletteFull(!inPalette(engine, pigmentIdFor(engine, activeColor)));
+        }
         const draw = (me) => handleInteraction(me, false);
         const stop = () => {
             lastPosRef.current = null;
             lastTimeRef.current = null;
+            penRef.current = null;
             velocityRef.current = 0;
             dynamicSizeRef.current = brushMode === 'background' ? backgroundBrush.size : brush.size;
-            window.removeEventListener('mousemove', draw);
-            window.removeEventListener('mouseup', stop);
+            engineRef.current?.end_stroke();
+            if (engineRef.current) {
+                setBrushLoad({
+                    water: engineRef.current.brush_water(),
+                    pigment: engineRef.current.brush_pigment(),
+                });
+            }
+            window.removeEventListener('pointermove', draw);
+            window.removeEventListener('pointerup', stop);
+            window.removeEventListener('pointercancel', stop);
         };
-        window.addEventListener('mousemove', draw);
-        window.addEventListener('mouseup', stop);
-    }, [handleInteraction, brushMode, brush.size, backgroundBrush.size]);
+        window.addEventListener('pointermove', draw);
+        window.addEventListener('pointerup', stop);
+        window.addEventListener('pointercancel', stop);
+    }, [handleInteraction, brushMode, brush.size, backgroundBrush.size, activeColor]);
+
+    // 머금은 붓: 현재 색을 묻히거나 헹군다
+    const handleLoadBrush = useCallback(() => {
+        const engine = engineRef.current;
+        if (!engine) return;
+        engine.load_brush(brush.water, brush.pigment, pigmentIdFor(engine, activeColor));
+        setBrushLoad({ water: engine.brush_water(), pigment: engine.brush_pigment() });
+    }, [brush.water, brush.pigment, activeColor]);
+
+    const handleRinseBrush = useCallback(() => {
+        const engine = engineRef.current;
+        if (!engine) return;
+        engine.rinse_brush();
+        setBrushLoad({ water: engine.brush_water(), pigment: engine.brush_pigment() });
+    }, []);
 
     const handleCanvasMouseMove = useCallback((e) => drawCursor(e.clientX, e.clientY), [drawCursor]);
     const handleCanvasMouseLeave = useCallback(() => {
@@ -406,15 +556,128 @@ export default function App() {
         renderFrame();
     }, [renderFrame]);
 
+    const handleUndo = useCallback(() => {
+        const e = engineRef.current;
+        if (e && e.undo()) renderFrame();
+    }, [renderFrame]);
+
+    const handleRedo = useCallback(() => {
+        const e = engineRef.current;
+        if (e && e.redo()) renderFrame();
+    }, [renderFrame]);
+
+    // Ctrl+Z / Ctrl+Shift+Z (Ctrl+Y)
+    useEffect(() => {
+        const onKey = (ev) => {
+            if (!(ev.ctrlKey || ev.metaKey)) return;
+            const key = ev.key.toLowerCase();
+            if (key === 'z' && !ev.shiftKey) {
+                ev.preventDefault();
+                handleUndo();
+            } else if ((key === 'z' && ev.shiftKey) || key === 'y') {
+                ev.preventDefault();
+                handleRedo();
+            }
+        };
+        window.addEventListener('keydown', onKey);
+        return () => window.removeEventListener('keydown', onKey);
+    }, [handleUndo, handleRedo]);
+
+    // 프로젝트 저장/열기 (젖은 물감 상태까지 그대로 이어서 작업)
+    const handleSaveProject = useCallback(() => {
+        const e = engineRef.current;
+        if (!e) return;
+        const blob = new Blob([e.save_state()], { type: 'application/octet-stream' });
+        const link = document.createElement('a');
+        link.download = `watercolor_${Date.now()}.wcen`;
+        link.href = URL.createObjectURL(blob);
+        link.click();
+        URL.revokeObjectURL(link.href);
+    }, []);
+
+    const handleLoadProject = useCallback(async (ev) => {
+        const file = ev.target.files[0];
+        ev.target.value = '';
+        const e = engineRef.current;
+        if (!file || !e) return;
+        try {
+            e.load_state(new Uint8Array(await file.arrayBuffer()));
+            setCanvasWidth(e.get_width());
+            setCanvasHeight(e.get_height());
+            renderFrame();
+        } catch (err) {
+            console.error('프로젝트 열기 실패:', err);
+        }
+    }, [renderFrame]);
+
+    // 붓 기록: 기록 시작 -> 저장(.wcjn) -> 재생으로 같은 그림을 다시 만든다
+    const handleStartJournal = useCallback(() => {
+        engineRef.current?.start_journal();
+    }, []);
+
+    const handleSaveJournal = useCallback(() => {
+        const e = engineRef.current;
+        if (!e || !e.is_journaling()) return;
+        const blob = new Blob([e.export_journal()], { type: 'application/octet-stream' });
+        const link = document.createElement('a');
+        link.download = `watercolor_${Date.now()}.wcjn`;
+        link.href = URL.createObjectURL(blob);
+        link.click();
+        URL.revokeObjectURL(link.href);
+    }, []);
+
+    const handleReplayJournal = useCallback(async (ev) => {
+        const file = ev.target.files[0];
+        ev.target.value = '';
+        const e = engineRef.current;
+        if (!file || !e) return;
+        try {
+            e.replay(new Uint8Array(await file.arrayBuffer()));
+            setCanvasWidth(e.get_width());
+            setCanvasHeight(e.get_height());
+            renderFrame();
+        } catch (err) {
+            console.error('붓 기록 재생 실패:', err);
+        }
+    }, [renderFrame]);
+
+    // 지금까지의 붓 기록을 scale배 해상도의 새 엔진에서 다시 그려 PNG로 받는다 (작게 칠하고 크게 출력)
+    const handleExportScaled = useCallback((scale) => {
+        const e = engineRef.current;
+        const wasm = wasmModuleRef.current;
+        if (!e || !wasm) return;
+        if (e.journal_len() === 0) {
+            console.warn('고해상도 출력에는 붓 기록이 필요합니다');
+            return;
+        }
+        const big = new wasm.WatercolorEngine(1, 1);
+        try {
+            big.replay_scaled(e.export_journal(), scale);
+            const blob = new Blob([big.export_png(true)], { type: 'image/png' });
+            const link = document.createElement('a');
+            link.download = `watercolor_${Date.now()}_x${scale}.png`;
+            link.href = URL.createObjectURL(blob);
+            link.click();
+            URL.revokeObjectURL(link.href);
+        } catch (err) {
+            console.error('고해상도 재생 실패:', err);
+        } finally {
+            big.free();
+        }
+    }, []);
+
+    // 엔진이 직접 PNG를 만든다 (버전, 물리 설정, 붓 기록 해시 메타데이터 포함)
     const handleDownload = useCallback(() => {
-        const canvas = canvasRef.current;
-        if (!canvas) return;
+        const e = engineRef.current;
+        if (!e) return;
+        const blob = new Blob([e.export_png(true)], { type: 'image/png' });
         const link = document.createElement('a');
         const now = new Date();
         const ts = `${now.getFullYear()}${String(now.getMonth() + 1).padStart(2, '0')}${String(now.getDate()).padStart(2, '0')}_${String(now.getHours()).padStart(2, '0')}${String(now.getMinutes()).padStart(2, '0')}${String(now.getSeconds()).padStart(2, '0')}`;
         link.download = `watercolor_${ts}.png`;
-        link.href = canvas.toDataURL('image/png');
+        link.href = URL.createObjectURL(blob);
         link.click();
+        URL.revokeObjectURL(link.href);
     }, []);
 
     // --- 에러/로딩 ---
@@ -571,7 +834,7 @@ export default function App() {
                                 <ControlSlider label="배경 수분량" value={backgroundBrush.water} min={0.1} max={3.0} step={0.1}
                                     onChange={(v) => setBackgroundBrush({ ...backgroundBrush, water: v })} />
                             )}
-                            {brushMode === 'paint' && (
+                            {(brushMode === 'paint' || brushMode === 'loaded') && (
                                 <ControlSlider label="안료 농도" value={brush.pigment} min={0.05} max={2.0} step={0.05}
                                     onChange={(v) => setBrush({ ...brush, pigment: v })} />
                             )}
@@ -587,6 +850,19 @@ export default function App() {
                                 <ControlSlider label="블렌딩 강도" value={blendStrength} min={0.05} max={1.0} step={0.05}
                                     onChange={setBlendStrength} />
                             )}
+                            {brushMode === 'loaded' && (
+                                <div className="sidebar-actions">
+                                    <span className="slider-label">
+                                        붓에 남은 물 {brushLoad.water.toFixed(2)} / 안료 {brushLoad.pigment.toFixed(2)}
+                                    </span>
+                                    <button className="btn-download-full" onClick={handleLoadBrush}>
+                                        🖌 물감 묻히기
+                                    </button>
+                                    <button className="btn-download-full" onClick={handleRinseBrush}>
+                                        💧 붓 헹구기
+                                    </button>
+                                </div>
+                            )}
                             {brushMode === 'water' && (
                                 <ControlSlider label="번짐 강도" value={waterFlow} min={0.1} max={2.0} step={0.1}
                                     onChange={setWaterFlow} />
@@ -596,6 +872,28 @@ export default function App() {
                                     onChange={(v) => setBrush({ ...brush, speedSensitivity: v })} />
                             )}
                         </div>
+                        <div className="toggle-row" onClick={() => setDrybrush(!drybrush)}>
+                            <span className="toggle-label">마른 붓 (수분량 0.3 미만은 종이 결에만)</span>
+                            <div className={`toggle-track ${drybrush ? 'on' : 'off'}`}>
+                                <div className="toggle-thumb" />
+                            </div>
+                        </div>
+                        <div className="ratio-grid">
+                            {BRUSH_TIPS.map((tip) => (
+                                <button
+                                    key={tip.key}
+                                    className={`ratio-btn ${brushTip === tip.key ? 'active' : ''}`}
+                                    onClick={() => setBrushTip(tip.key)}
+                                >
+                                    <span className="ratio-label">{tip.label}</span>
+                                </button>
+                            ))}
+                            <label className={`ratio-btn ${brushTip === CUSTOM_TIP ? 'active' : ''}`}>
+                                <span className="ratio-label">붓끝 이미지</span>
+                                <input type="file" accept="image/*" onChange={handleTipUpload}
+                                    style={{ display: 'none' }} />
+                            </label>
+                        </div>
                     </section>
 
                     {/* 물리 엔진 */}
@@ -625,6 +923,8 @@ export default function App() {
                                 onChange={(v) => setPigmentProps({ ...pigmentProps, adhesion: v })} />
                             <ControlSlider label="과립화" value={pigmentProps.granularity} min={0} max={2.0} step={0.1}
                                 onChange={(v) => setPigmentProps({ ...pigmentProps, granularity: v })} />
+                            <ControlSlider label="디버그 뷰 (0 일반 / 1 과립 / 2 응집)" value={debugView} min={0} max={2} step={1}
+                                onChange={(v) => setDebugView(v)} />
                         </div>
                     </section>
 
@@ -682,6 +982,37 @@ export default function App() {
                             <button className="btn-download-full" onClick={handleDownload}>
                                 <Download size={18} /> 이미지 다운로드
                             </button>
+                            <button className="btn-download-full" onClick={handleSaveProject}>
+                                <Download size={18} /> 프로젝트 저장
+                            </button>
+                            <label className="btn-download-full">
+                                📂 프로젝트 열기
+                                <input type="file" accept=".wcen" onChange={handleLoadProject}
+                                    style={{ display: 'none' }} />
+                            </label>
+                            <button className="btn-download-full" onClick={handleStartJournal}>
+                                ⏺ 붓 기록 시작
+                            </button>
+                            <button className="btn-download-full" onClick={handleSaveJournal}>
+                                <Download size={18} /> 붓 기록 저장
+                            </button>
+                            <label className="btn-download-full">
+                                ▶ 붓 기록 재생
+                                <input type="file" accept=".wcjn" onChange={handleReplayJournal}
+                                    style={{ display: 'none' }} />
+                            </label>
+                            <button className="btn-download-full" onClick={() => handleExportScaled(2)}>
+                                <Download size={18} /> 붓 기록 2배 PNG
+                            </button>
+                            <button className="btn-download-full" onClick={() => handleExportScaled(4)}>
+                                <Download size={18} /> 붓 기록 4배 PNG
+                            </button>
+                            <button className="btn-download-full" onClick={handleUndo}>
+                                <Undo2 size={18} /> 되돌리기
+                            </button>
+                            <button className="btn-download-full" onClick={handleRedo}>
+                                <Redo2 size={18} /> 다시하기
+                            </button>
                             <button className="btn-reset-full" onClick={handleReset}>
                                 <RefreshCcw size={18} /> 캔버스 초기화
                             </button>
@@ -693,12 +1024,14 @@ export default function App() {
                 <section className="canvas-area" ref={canvasAreaRef}>
                     <div className="canvas-frame" style={{ width: frameW, height: frameH }}>
                         <canvas ref={canvasRef} width={displayW} height={displayH}
-                            onMouseDown={handleMouseDown} />
+                            style={{ touchAction: 'none' }}
+                            onPointerDown={handlePointerDown} />
                         <canvas ref={cursorCanvasRef} className="cursor-canvas"
                             width={displayW} height={displayH}
-                            onMouseMove={handleCanvasMouseMove}
-                            onMouseLeave={handleCanvasMouseLeave}
-                            onMouseDown={handleMouseDown} />
+                            style={{ touchAction: 'none' }}
+                            onPointerMove={handleCanvasMouseMove}
+                            onPointerLeave={handleCanvasMouseLeave}
+                            onPointerDown={handlePointerDown} />
                     </div>
 
                     <div className="status-bar">
@@ -726,6 +1059,12 @@ export default function App() {
                             <span className="status-label">PAINT</span>
                             <span className="status-value">{freePaintMode ? '자유(구버전)' : '실루엣 제약'}</span>
                         </div>
+                        {paletteFull && (
+                            <div className="status-item">
+                                <span className="status-label">PALETTE</span>
+                                <span className="status-value">안료 8개가 모두 쓰여 새 색은 칠해지지 않습니다</span>
+                            </div>
+                        )}
                     </div>
                 </section>
             </main>
@@ -0,0 +1,126 @@
+// step/render 시간 측정 (기본 800x800)
+//
+//   cargo run --release --example bench [-- SIZE [ROUNDS]]
+//   cargo run --release --example bench --features scalar    SIMD 명령 대신 배열 구현 (묶음 구조는 같다)
+//   cargo run --release --example bench --features parallel  행 단위 병렬
+//
+// 여러 안료를 칠한 뒤 물이 마르기 전까지 step과 render를 번갈아 돌리고, 가장 빠른 회차와 평균을 적는다.
+// 붓 자국 하나만 있는 캔버스의 step도 잰다 (젖은 타일만 계산하므로 캔버스 크기와 거의 무관하다).
+
+use std::time::{Duration, Instant};
+use watercolor_engine::WatercolorEngine;
+
+fn paint(size: u32) -> WatercolorEngine {
+    let mut e = WatercolorEngine::new(size, size);
+    let ultramarine = e.find_pigment("ultramarine") as u32;
+    let alizarin = e.find_pigment("alizarin") as u32;
+    let gamboge = e.find_pigment("gamboge") as u32;
+    let s = size as i32;
+    let f = size as f32;
+    e.apply_background_brush_stroke(0, s / 2, s, s / 2, f * 0.4, 0.8, 0.4, gamboge);
+    e.apply_brush_stroke(
+        s / 10,
+        s / 5,
+        s * 9 / 10,
+        s / 4,
+        f * 0.025,
+        0.9,
+        0.8,
+        ultramarine,
+        0.0,
+    );
+    e.apply_water_brush_stroke(
+        s / 6,
+        s * 2 / 3,
+        s * 5 / 6,
+        s * 2 / 3,
+        f * 0.03,
+        1.0,
+        1.0,
+        0.0,
+    );
+    e.apply_brush_stroke(
+        s / 4,
+        s / 10,
+        s / 2,
+        s * 9 / 10,
+        f * 0.02,
+        0.8,
+        0.9,
+        alizarin,
+        3.0,
+    );
+    e
+}
+
+// 가운데에 짧은 붓 자국 하나
+fn dab(size: u32) -> WatercolorEngine {
+    let mut e = WatercolorEngine::new(size, size);
+    let ultramarine = e.find_pigment("ultramarine") as u32;
+    let c = size as i32 / 2;
+    e.apply_brush_stroke(c - 40, c, c + 40, c + 20, 12.0, 0.9, 0.8, ultramarine, 1.0);
+    e
+}
+
+fn report(name: &str, times: &[Duration]) {
+    let best = times.iter().min().copied().unwrap_or_default();
+    let mean = times.iter().sum::<Duration>() / times.len().max(1) as u32;
+    println!(
+        "{:<7} 최소 {:>8.2} ms   평균 {:>8.2} ms",
+        name,
+        best.as_secs_f64() * 1000.0,
+        mean.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let size: u32 = args.next().and_then(|a| a.parse().ok()).unwrap_or(800);
+    let rounds: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(20);
+
+    let mut e = paint(size);
+    // 물이 퍼지기 시작한 상태에서 잰다
+    for _ in 0..5 {
+        e.step();
+    }
+    let mut steps = Vec::with_capacity(rounds);
+    let mut renders = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+        let t = Instant::now();
+        e.step();
+        steps.push(t.elapsed());
+        let t = Instant::now();
+        std::hint::black_box(e.render());
+        renders.push(t.elapsed());
+    }
+
+    let mut e = dab(size);
+    for _ in 0..5 {
+        e.step();
+    }
+    let mut dabs = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+        let t = Instant::now();
+        e.step();
+        dabs.push(t.elapsed());
+    }
+
+    let simd = if cfg!(feature = "scalar") {
+        "스칼라"
+    } else {
+        "SIMD"
+    };
+    let threads = if cfg!(feature = "parallel") {
+        "병렬"
+    } else {
+        "직렬"
+    };
+    println!("{}x{}, {}회, {}/{}", size, size, rounds, simd, threads);
+    report("step", &steps);
+    report("render", &renders);
+    report("step*", &dabs);
+    println!(
+        "* 붓 자국 하나, 깨어 있는 타일 {:.1}%",
+        e.active_area() * 100.0
+    );
+}
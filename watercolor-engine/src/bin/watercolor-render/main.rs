@@ -0,0 +1,331 @@
+// watercolor-render: 브라우저 없이 붓 스크립트를 실행해 PNG로 저장한다.
+//
+//   watercolor-render SCRIPT -o OUT.png [--paper PAPER.pgm] [--size WxH]
+//
+// SCRIPT 가 '-'이면 표준 입력에서 읽는다. 스크립트 문법은 script.rs 참고.
+// 출력 확장자가 .ppm이면 PPM(P6)으로, 그 밖에는 메타데이터를 넣은 PNG로 저장한다.
+
+mod script;
+
+use script::Command;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use watercolor_engine::{WatercolorEngine, MAX_CELLS};
+
+const DEFAULT_SIZE: (u32, u32) = (420, 420);
+
+const USAGE: &str = "사용법: watercolor-render SCRIPT -o OUT.png [--paper PAPER.pgm] [--size WxH]";
+
+struct Options {
+    script: String,
+    output: PathBuf,
+    paper: Option<PathBuf>,
+    size: (u32, u32),
+}
+
+fn parse_size(text: &str) -> Option<(u32, u32)> {
+    let (w, h) = text.split_once(['x', 'X'])?;
+    let size = (w.parse().ok()?, h.parse().ok()?);
+    (size.0 > 0 && size.1 > 0).then_some(size)
+}
+
+// 상태 파일과 같은 한도. 메모리를 잡기 전에 거절한다.
+fn check_size(w: u32, h: u32) -> Result<(), String> {
+    if w == 0 || h == 0 {
+        return Err("캔버스 크기는 0보다 커야 합니다".to_string());
+    }
+    if w as u64 * h as u64 > MAX_CELLS {
+        return Err(format!(
+            "캔버스가 너무 큽니다 ({}x{}, 최대 {}칸)",
+            w, h, MAX_CELLS
+        ));
+    }
+    Ok(())
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut script = None;
+    let mut output = None;
+    let mut paper = None;
+    let mut size = DEFAULT_SIZE;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().map(PathBuf::from),
+            "--paper" => paper = args.next().map(PathBuf::from),
+            "--size" => {
+                let text = args.next().unwrap_or_default();
+                size = parse_size(&text).ok_or_else(|| format!("잘못된 크기 '{}'", text))?;
+                check_size(size.0, size.1)?;
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if script.is_none() => script = Some(arg),
+            _ => return Err(format!("알 수 없는 인자 '{}'\n{}", arg, USAGE)),
+        }
+    }
+    Ok(Options {
+        script: script.ok_or(USAGE)?,
+        output: output.ok_or(USAGE)?,
+        paper,
+        size,
+    })
+}
+
+// PGM/PPM (P2, P3, P5, P6, maxval <= 255)을 load_paper_texture가 받는 RGBA로 읽는다.
+fn read_pnm(path: &Path) -> Result<(Vec<u8>, u32, u32), String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let err = |msg: &str| format!("{}: {}", path.display(), msg);
+
+    // 헤더 토큰 4개 (형식, 너비, 높이, 최댓값), # 주석 허용
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        while pos < data.len() && data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos < data.len() && data[pos] == b'#' {
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(err("PNM 헤더가 끊겼습니다"));
+        }
+        header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+    }
+    let number = |s: &str| s.parse::<u32>().map_err(|_| err("PNM 헤더 숫자 오류"));
+    let (width, height, maxval) = (
+        number(&header[1])?,
+        number(&header[2])?,
+        number(&header[3])?,
+    );
+    if width == 0 || height == 0 || maxval == 0 || maxval > 255 {
+        return Err(err("지원하지 않는 PNM (크기 0 또는 16비트)"));
+    }
+    let (channels, binary) = match header[0].as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(err("PGM/PPM 파일이 아닙니다")),
+    };
+    let count = width as usize * height as usize * channels;
+    let samples: Vec<u8> = if binary {
+        // 헤더 뒤 공백 한 바이트
+        let body = data
+            .get(pos + 1..pos + 1 + count)
+            .ok_or_else(|| err("픽셀 데이터가 부족합니다"))?;
+        body.to_vec()
+    } else {
+        let text = String::from_utf8_lossy(&data[pos..]);
+        let values: Result<Vec<u8>, _> = text
+            .split_whitespace()
+            .take(count)
+            .map(|t| t.parse::<u32>().map(|v| v.min(maxval) as u8))
+            .collect();
+        let values = values.map_err(|_| err("픽셀 값 오류"))?;
+        if values.len() < count {
+            return Err(err("픽셀 데이터가 부족합니다"));
+        }
+        values
+    };
+
+    let scale = |v: u8| ((v as u32 * 255) / maxval) as u8;
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for px in samples.chunks(channels) {
+        let (r, g, b) = if channels == 1 {
+            (px[0], px[0], px[0])
+        } else {
+            (px[0], px[1], px[2])
+        };
+        rgba.extend_from_slice(&[scale(r), scale(g), scale(b), 255]);
+    }
+    Ok((rgba, width, height))
+}
+
+struct Runner {
+    engine: Option<WatercolorEngine>,
+    size: (u32, u32),
+    paper: Option<(Vec<u8>, u32, u32)>,
+    pigment: u32,
+}
+
+impl Runner {
+    // 첫 그리기 명령에서 캔버스를 만들고 --paper 텍스처를 깐다
+    fn engine(&mut self) -> &mut WatercolorEngine {
+        let size = self.size;
+        let paper = &self.paper;
+        self.engine.get_or_insert_with(|| {
+            let mut e = WatercolorEngine::new(size.0, size.1);
+            if let Some((data, w, h)) = paper {
+                e.load_paper_texture(data, *w, *h);
+            }
+            e
+        })
+    }
+
+    fn run(&mut self, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Canvas(w, h) => {
+                if self.engine.is_some() {
+                    return Err("canvas는 그리기 명령보다 먼저 와야 합니다".to_string());
+                }
+                check_size(*w, *h)?;
+                self.size = (*w, *h);
+            }
+            Command::Paper(path) => {
+                let (data, w, h) = read_pnm(path)?;
+                self.engine().load_paper_texture(&data, w, h);
+            }
+            Command::Journal(path) => {
+                let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                self.engine().replay(&data)?;
+            }
+            Command::Physics(dt, evap, visc, pressure, iters) => self
+                .engine()
+                .set_physics(*dt, *evap, *visc, *pressure, *iters),
+            Command::Capillary(a, d) => self.engine().set_capillary_props(*a, *d),
+            Command::Bloom(s) => self.engine().set_bloom_strength(*s),
+            Command::Edge(s) => self.engine().set_edge_darkening(*s),
+            Command::Texture(on) => self.engine().set_show_texture(*on),
+            Command::Drybrush(on) => self.engine().set_drybrush(*on),
+            Command::Tip(preset) => self.engine().set_brush_tip(*preset),
+            Command::TipImage(path) => {
+                let (data, w, h) = read_pnm(path)?;
+                self.engine().load_brush_tip(&data, w, h);
+            }
+            Command::PigmentProps(a, g) => self.engine().set_pigment_props(*a, *g),
+            Command::Pigment(name) => {
+                let id = self.engine().find_pigment(name);
+                if id < 0 {
+                    return Err(format!("알 수 없는 안료 '{}'", name));
+                }
+                self.pigment = id as u32;
+            }
+            Command::PigmentRgb(name, [r, g, b], [density, staining, gran, floc]) => {
+                self.pigment = self
+                    .engine()
+                    .register_pigment_rgb(name, *r, *g, *b, *density, *staining, *gran, *floc);
+            }
+            Command::Brush([x, y], [size, water, amount, angle, pressure]) => {
+                let pigment = self.pigment;
+                self.engine()
+                    .apply_brush(*x, *y, *size, *water, *amount, pigment, *angle, *pressure)
+            }
+            Command::Stroke([x0, y0, x1, y1], [size, water, amount, velocity]) => {
+                let pigment = self.pigment;
+                self.engine().apply_brush_stroke(
+                    *x0, *y0, *x1, *y1, *size, *water, *amount, pigment, *velocity,
+                )
+            }
+            Command::Pen(
+                [x0, y0, x1, y1],
+                [size, water, amount, p0, p1, tilt_x, tilt_y, twist],
+            ) => {
+                let pigment = self.pigment;
+                self.engine().apply_pen_stroke(
+                    *x0, *y0, *x1, *y1, *size, *water, *amount, pigment, *p0, *tilt_x, *tilt_y,
+                    *twist, *p1, *tilt_x, *tilt_y, *twist,
+                )
+            }
+            Command::Spline([size, water, amount], samples) => {
+                let pigment = self.pigment;
+                self.engine()
+                    .apply_spline_stroke(samples, *size, *water, *amount, pigment)
+            }
+            Command::Load(water, amount) => {
+                let pigment = self.pigment;
+                self.engine().load_brush(*water, *amount, pigment)
+            }
+            Command::Rinse => self.engine().rinse_brush(),
+            Command::Loaded([x0, y0, x1, y1], [size, velocity]) => self
+                .engine()
+                .apply_loaded_brush_stroke(*x0, *y0, *x1, *y1, *size, *velocity),
+            Command::Wash([x0, y0, x1, y1], [size, water, amount]) => {
+                let pigment = self.pigment;
+                self.engine().apply_background_brush_stroke(
+                    *x0, *y0, *x1, *y1, *size, *water, *amount, pigment,
+                )
+            }
+            Command::Water([x0, y0, x1, y1], [size, water, flow, velocity]) => self
+                .engine()
+                .apply_water_brush_stroke(*x0, *y0, *x1, *y1, *size, *water, *flow, *velocity),
+            Command::Fade([x0, y0, x1, y1], [size, strength, velocity]) => self
+                .engine()
+                .apply_fade_brush_stroke(*x0, *y0, *x1, *y1, *size, *strength, *velocity),
+            Command::Blend([x0, y0, x1, y1], [size, strength, velocity]) => self
+                .engine()
+                .apply_blend_brush_stroke(*x0, *y0, *x1, *y1, *size, *strength, *velocity),
+            Command::Step(n) => {
+                let e = self.engine();
+                for _ in 0..*n {
+                    e.step();
+                }
+            }
+            Command::Undo => {
+                self.engine().undo();
+            }
+            Command::Redo => {
+                self.engine().redo();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let (source, base_dir) = if options.script == "-" {
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)
+            .map_err(|e| format!("표준 입력: {}", e))?;
+        (text, PathBuf::from("."))
+    } else {
+        let path = Path::new(&options.script);
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        (text, dir)
+    };
+    let lines = script::parse(&source, &base_dir)?;
+
+    let mut runner = Runner {
+        engine: None,
+        size: options.size,
+        paper: options.paper.as_deref().map(read_pnm).transpose()?,
+        pigment: 0,
+    };
+    for line in &lines {
+        runner
+            .run(&line.command)
+            .map_err(|e| format!("{}번째 줄: {}", line.number, e))?;
+    }
+
+    let engine = runner.engine();
+    let ppm = options
+        .output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ppm"));
+    let bytes = if ppm {
+        engine.export_ppm()
+    } else {
+        engine.export_png(true)
+    };
+    std::fs::write(&options.output, bytes)
+        .map_err(|e| format!("{}: {}", options.output.display(), e))
+}
+
+fn main() -> ExitCode {
+    let result = parse_options().and_then(|options| run(&options));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
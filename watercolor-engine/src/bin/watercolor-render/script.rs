@@ -0,0 +1,311 @@
+// 붓 스크립트 (한 줄에 명령 하나, # 뒤는 주석)
+//
+//   canvas W H                              캔버스 크기 (그리기 명령보다 먼저)
+//   paper PATH                              종이 텍스처 (PGM/PPM, 스크립트 기준 상대 경로)
+//   journal PATH                            붓 기록(.wcjn)을 재생해 캔버스를 통째로 바꾼다
+//   physics DT EVAP VISC PRESSURE ITERS
+//   capillary ABSORPTION DIFFUSION
+//   bloom STRENGTH | edge STRENGTH | texture on|off | drybrush on|off
+//   tip round|flat|filbert|fan|rigger|PATH  붓끝 모양 (PATH는 PGM/PPM, 어두운 곳이 붓털)
+//   pigment_props ADHESION GRANULARITY
+//   pigment NAME                            이후 붓에 쓸 안료 선택
+//   pigment_rgb NAME R G B [DENSITY STAINING GRANULATION FLOCCULATION]   (R G B는 0..1)
+//   brush X Y SIZE WATER AMOUNT [ANGLE PRESSURE]
+//   stroke X0 Y0 X1 Y1 SIZE WATER AMOUNT [VELOCITY]
+//   pen X0 Y0 X1 Y1 SIZE WATER AMOUNT P0 P1 [TILT_X TILT_Y TWIST]   펜 획 (압력 P0→P1, 각도는 도)
+//   spline SIZE WATER AMOUNT X Y PRESSURE TIME [X Y PRESSURE TIME ...]   곡선 획 (TIME은 ms)
+//   load WATER AMOUNT                       머금은 붓에 현재 안료를 묻힌다
+//   rinse                                   머금은 붓을 헹군다
+//   loaded X0 Y0 X1 Y1 SIZE [VELOCITY]      머금은 붓 획 (붓에 남은 물감만큼 칠한다)
+//   wash X0 Y0 X1 Y1 SIZE WATER AMOUNT       배경 붓
+//   water X0 Y0 X1 Y1 SIZE WATER FLOW [VELOCITY]
+//   fade X0 Y0 X1 Y1 SIZE STRENGTH [VELOCITY]
+//   blend X0 Y0 X1 Y1 SIZE STRENGTH [VELOCITY]
+//   step [N]
+//   undo | redo
+
+use std::path::PathBuf;
+
+// set_brush_tip 번호 순서
+const TIPS: [&str; 5] = ["round", "flat", "filbert", "fan", "rigger"];
+
+pub enum Command {
+    Canvas(u32, u32),
+    Paper(PathBuf),
+    Journal(PathBuf),
+    Physics(f32, f32, f32, f32, u32),
+    Capillary(f32, f32),
+    Bloom(f32),
+    Edge(f32),
+    Texture(bool),
+    Drybrush(bool),
+    Tip(u32),
+    TipImage(PathBuf),
+    PigmentProps(f32, f32),
+    Pigment(String),
+    PigmentRgb(String, [f32; 3], [f32; 4]),
+    Brush([i32; 2], [f32; 5]),
+    Stroke([i32; 4], [f32; 4]),
+    Pen([i32; 4], [f32; 8]),
+    Spline([f32; 3], Vec<f32>),
+    Load(f32, f32),
+    Rinse,
+    Loaded([i32; 4], [f32; 2]),
+    Wash([i32; 4], [f32; 3]),
+    Water([i32; 4], [f32; 4]),
+    Fade([i32; 4], [f32; 3]),
+    Blend([i32; 4], [f32; 3]),
+    Step(u32),
+    Undo,
+    Redo,
+}
+
+pub struct Line {
+    pub number: usize,
+    pub command: Command,
+}
+
+struct Args<'a> {
+    name: &'a str,
+    words: Vec<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn check(&self, min: usize, max: usize) -> Result<(), String> {
+        let n = self.words.len();
+        if n < min || n > max {
+            return Err(if min == max {
+                format!("{}: 인자 {}개가 필요합니다 ({}개)", self.name, min, n)
+            } else {
+                format!(
+                    "{}: 인자 {}~{}개가 필요합니다 ({}개)",
+                    self.name, min, max, n
+                )
+            });
+        }
+        Ok(())
+    }
+
+    fn f32(&self, k: usize) -> Result<f32, String> {
+        self.words[k]
+            .parse()
+            .map_err(|_| format!("{}: 숫자가 아닙니다 '{}'", self.name, self.words[k]))
+    }
+
+    fn f32_or(&self, k: usize, default: f32) -> Result<f32, String> {
+        if k < self.words.len() {
+            self.f32(k)
+        } else {
+            Ok(default)
+        }
+    }
+
+    fn i32(&self, k: usize) -> Result<i32, String> {
+        Ok(self.f32(k)?.round() as i32)
+    }
+
+    fn u32(&self, k: usize) -> Result<u32, String> {
+        self.words[k].parse().map_err(|_| {
+            format!(
+                "{}: 0 이상의 정수가 아닙니다 '{}'",
+                self.name, self.words[k]
+            )
+        })
+    }
+
+    fn ints<const N: usize>(&self) -> Result<[i32; N], String> {
+        let mut out = [0; N];
+        for (k, o) in out.iter_mut().enumerate() {
+            *o = self.i32(k)?;
+        }
+        Ok(out)
+    }
+}
+
+fn parse_command(args: &Args, base_dir: &std::path::Path) -> Result<Command, String> {
+    Ok(match args.name {
+        "canvas" => {
+            args.check(2, 2)?;
+            Command::Canvas(args.u32(0)?, args.u32(1)?)
+        }
+        "paper" => {
+            args.check(1, 1)?;
+            Command::Paper(base_dir.join(args.words[0]))
+        }
+        "journal" => {
+            args.check(1, 1)?;
+            Command::Journal(base_dir.join(args.words[0]))
+        }
+        "physics" => {
+            args.check(5, 5)?;
+            Command::Physics(
+                args.f32(0)?,
+                args.f32(1)?,
+                args.f32(2)?,
+                args.f32(3)?,
+                args.u32(4)?,
+            )
+        }
+        "capillary" => {
+            args.check(2, 2)?;
+            Command::Capillary(args.f32(0)?, args.f32(1)?)
+        }
+        "bloom" => {
+            args.check(1, 1)?;
+            Command::Bloom(args.f32(0)?)
+        }
+        "edge" => {
+            args.check(1, 1)?;
+            Command::Edge(args.f32(0)?)
+        }
+        "texture" | "drybrush" => {
+            args.check(1, 1)?;
+            let on = match args.words[0] {
+                "on" => true,
+                "off" => false,
+                other => return Err(format!("{}: on 또는 off ('{}')", args.name, other)),
+            };
+            if args.name == "texture" {
+                Command::Texture(on)
+            } else {
+                Command::Drybrush(on)
+            }
+        }
+        "tip" => {
+            args.check(1, 1)?;
+            match TIPS.iter().position(|&name| name == args.words[0]) {
+                Some(preset) => Command::Tip(preset as u32),
+                None => Command::TipImage(base_dir.join(args.words[0])),
+            }
+        }
+        "pigment_props" => {
+            args.check(2, 2)?;
+            Command::PigmentProps(args.f32(0)?, args.f32(1)?)
+        }
+        "pigment" => {
+            args.check(1, 1)?;
+            Command::Pigment(args.words[0].to_string())
+        }
+        "pigment_rgb" => {
+            args.check(4, 8)?;
+            let rgb = [args.f32(1)?, args.f32(2)?, args.f32(3)?];
+            let props = [
+                args.f32_or(4, 0.05)?,
+                args.f32_or(5, 0.4)?,
+                args.f32_or(6, 0.3)?,
+                args.f32_or(7, 0.1)?,
+            ];
+            Command::PigmentRgb(args.words[0].to_string(), rgb, props)
+        }
+        "brush" => {
+            args.check(5, 7)?;
+            Command::Brush(
+                args.ints()?,
+                [
+                    args.f32(2)?,
+                    args.f32(3)?,
+                    args.f32(4)?,
+                    args.f32_or(5, 0.0)?,
+                    args.f32_or(6, 1.0)?,
+                ],
+            )
+        }
+        "stroke" | "water" => {
+            args.check(7, 8)?;
+            let values = [
+                args.f32(4)?,
+                args.f32(5)?,
+                args.f32(6)?,
+                args.f32_or(7, 0.0)?,
+            ];
+            if args.name == "stroke" {
+                Command::Stroke(args.ints()?, values)
+            } else {
+                Command::Water(args.ints()?, values)
+            }
+        }
+        "pen" => {
+            args.check(9, 12)?;
+            let mut values = [0.0; 8];
+            for (k, v) in values.iter_mut().enumerate() {
+                *v = args.f32_or(4 + k, 0.0)?;
+            }
+            Command::Pen(args.ints()?, values)
+        }
+        "spline" => {
+            let n = args.words.len();
+            if n < 7 || !(n - 3).is_multiple_of(4) {
+                return Err(format!(
+                    "spline: 인자는 3개 + 표본마다 4개가 필요합니다 ({}개)",
+                    n
+                ));
+            }
+            let samples = (3..n).map(|k| args.f32(k)).collect::<Result<_, _>>()?;
+            Command::Spline([args.f32(0)?, args.f32(1)?, args.f32(2)?], samples)
+        }
+        "load" => {
+            args.check(2, 2)?;
+            Command::Load(args.f32(0)?, args.f32(1)?)
+        }
+        "rinse" => {
+            args.check(0, 0)?;
+            Command::Rinse
+        }
+        "loaded" => {
+            args.check(5, 6)?;
+            Command::Loaded(args.ints()?, [args.f32(4)?, args.f32_or(5, 0.0)?])
+        }
+        "wash" => {
+            args.check(7, 7)?;
+            Command::Wash(args.ints()?, [args.f32(4)?, args.f32(5)?, args.f32(6)?])
+        }
+        "fade" | "blend" => {
+            args.check(6, 7)?;
+            let values = [args.f32(4)?, args.f32(5)?, args.f32_or(6, 0.0)?];
+            if args.name == "fade" {
+                Command::Fade(args.ints()?, values)
+            } else {
+                Command::Blend(args.ints()?, values)
+            }
+        }
+        "step" => {
+            args.check(0, 1)?;
+            Command::Step(if args.words.is_empty() {
+                1
+            } else {
+                args.u32(0)?
+            })
+        }
+        "undo" => {
+            args.check(0, 0)?;
+            Command::Undo
+        }
+        "redo" => {
+            args.check(0, 0)?;
+            Command::Redo
+        }
+        other => return Err(format!("알 수 없는 명령 '{}'", other)),
+    })
+}
+
+pub fn parse(source: &str, base_dir: &std::path::Path) -> Result<Vec<Line>, String> {
+    let mut lines = Vec::new();
+    for (i, raw) in source.lines().enumerate() {
+        let text = raw.split('#').next().unwrap_or("").trim();
+        let mut words = text.split_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let args = Args {
+            name,
+            words: words.collect(),
+        };
+        let command =
+            parse_command(&args, base_dir).map_err(|e| format!("{}번째 줄: {}", i + 1, e))?;
+        lines.push(Line {
+            number: i + 1,
+            command,
+        });
+    }
+    Ok(lines)
+}
@@ -0,0 +1,48 @@
+// 물감을 머금은 붓 (apply_loaded_brush / apply_loaded_brush_stroke).
+// 붓 자국마다 종이에 내려놓은 만큼 줄고, 젖은 물감 위를 지나면 물과 부유 안료를 빨아들인다.
+// 그래서 획이 길어지면 말라 갈라지고, 다른 색 위를 지난 붓은 그 색을 묻혀 간다.
+// 붓은 캔버스가 아니라 화가의 도구라서 되돌리기로는 돌아가지 않는다.
+
+// 물감을 찍을 때 붓에 묻어 있던 다른 안료가 남는 비율
+const DIP_KEEP: f32 = 0.3;
+// 이보다 적게 남은 안료는 털어 낸다
+const TRACE: f32 = 1e-6;
+
+// 붓에 남은 양은 apply_brush의 water / pigment_amount와 같은 단위다
+#[derive(Clone, Default)]
+pub(crate) struct Brush {
+    pub water: f32,
+    // (안료 id, 양). 팔레트 슬롯은 되돌리기로 바뀔 수 있어서 안료 id로 들고 있는다.
+    pub pigment: Vec<(u32, f32)>,
+}
+
+impl Brush {
+    // 물감 접시에 붓을 찍는다. 물은 water, 찍은 안료는 amount가 되고 묻어 있던 다른 안료는 조금 남는다.
+    pub(crate) fn dip(&mut self, water: f32, id: u32, amount: f32) {
+        self.water = water.max(0.0);
+        self.pigment.iter_mut().for_each(|(_, a)| *a *= DIP_KEEP);
+        self.pigment.retain(|&(other, _)| other != id);
+        self.pigment.push((id, amount.max(0.0)));
+        self.shake();
+    }
+
+    // 물통에 헹군다 (안료만 빠지고 물은 그대로)
+    pub(crate) fn rinse(&mut self) {
+        self.pigment.clear();
+    }
+
+    pub(crate) fn add(&mut self, id: u32, amount: f32) {
+        match self.pigment.iter_mut().find(|(other, _)| *other == id) {
+            Some((_, a)) => *a += amount,
+            None => self.pigment.push((id, amount)),
+        }
+    }
+
+    pub(crate) fn shake(&mut self) {
+        self.pigment.retain(|&(_, a)| a > TRACE);
+    }
+
+    pub(crate) fn total_pigment(&self) -> f32 {
+        self.pigment.iter().map(|&(_, a)| a).sum()
+    }
+}
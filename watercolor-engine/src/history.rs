@@ -0,0 +1,360 @@
+// 되돌리기/다시하기 기록
+// 가장 최근 체크포인트를 전체 상태 사본(shadow) 하나로 들고 있고, 그 이전 체크포인트들은
+// 이웃 체크포인트와 달라진 타일(TILE x TILE 셀)만 보관한다.
+// 체크포인트 사이에 바뀌었을 수 있는 타일은 타일 활동 지도(Tiles::take_edited)가 알려 주므로 그 타일만 비교하고,
+// 기록 전체가 차지하는 바이트가 예산을 넘으면 가장 오래된 차이부터 버린다.
+
+use crate::tiles::TILE;
+
+// 되돌리기가 다루는 엔진 필드 (유체 + 모세관 + 안료 평면과 팔레트)
+pub(crate) struct Fields<'a> {
+    pub h: &'a mut Vec<f32>,
+    pub u: &'a mut Vec<f32>,
+    pub v: &'a mut Vec<f32>,
+    pub p: &'a mut Vec<f32>,
+    pub mask: &'a mut Vec<f32>,
+    pub s: &'a mut Vec<f32>,
+    pub g: &'a mut Vec<Vec<f32>>,
+    pub d: &'a mut Vec<Vec<f32>>,
+    pub palette: &'a mut Vec<u32>,
+}
+
+// 가장 최근 체크포인트의 사본
+struct Shadow {
+    h: Vec<f32>,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    p: Vec<f32>,
+    mask: Vec<f32>,
+    s: Vec<f32>,
+    g: Vec<Vec<f32>>,
+    d: Vec<Vec<f32>>,
+    palette: Vec<u32>,
+}
+
+impl Shadow {
+    fn of(f: &Fields) -> Shadow {
+        Shadow {
+            h: f.h.clone(),
+            u: f.u.clone(),
+            v: f.v.clone(),
+            p: f.p.clone(),
+            mask: f.mask.clone(),
+            s: f.s.clone(),
+            g: f.g.clone(),
+            d: f.d.clone(),
+            palette: f.palette.clone(),
+        }
+    }
+
+    fn fields(&mut self) -> Fields<'_> {
+        Fields {
+            h: &mut self.h,
+            u: &mut self.u,
+            v: &mut self.v,
+            p: &mut self.p,
+            mask: &mut self.mask,
+            s: &mut self.s,
+            g: &mut self.g,
+            d: &mut self.d,
+            palette: &mut self.palette,
+        }
+    }
+}
+
+impl Fields<'_> {
+    // 안료 평면을 n개로 맞춘다 (없는 평면은 0)
+    fn widen(&mut self, n: usize, total: usize) {
+        if self.g.len() < n {
+            self.g.resize(n, vec![0.0; total]);
+            self.d.resize(n, vec![0.0; total]);
+        }
+    }
+
+    // 팔레트에 없는 평면을 버린다. 두 상태를 맞추느라 늘린 평면은 0뿐이다.
+    fn fit(&mut self) {
+        self.g.truncate(self.palette.len());
+        self.d.truncate(self.palette.len());
+    }
+
+    // 경계면 속도, 셀 중심 평면, 안료 평면 순서. 속도는 경계면 필드라서 u는 열이, v는 행이 하나씩 더 있다.
+    fn planes(&mut self) -> Vec<(&mut Vec<f32>, Grid)> {
+        let mut out = vec![
+            (&mut *self.u, Grid::U),
+            (&mut *self.v, Grid::V),
+            (&mut *self.h, Grid::Cell),
+            (&mut *self.p, Grid::Cell),
+            (&mut *self.mask, Grid::Cell),
+            (&mut *self.s, Grid::Cell),
+        ];
+        for (g, d) in self.g.iter_mut().zip(self.d.iter_mut()) {
+            out.push((g, Grid::Cell));
+            out.push((d, Grid::Cell));
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Grid {
+    Cell,
+    U,
+    V,
+}
+
+// 한 상태를 다른 상태로 바꾸는 차이. 담긴 타일 밖은 두 상태가 같다.
+// swap으로 적용하면 바꾸기 전 값이 대신 담기므로 같은 차이로 되돌아갈 수 있다.
+struct Delta {
+    palette: Vec<u32>,
+    // 두 상태 중 많은 쪽의 안료 평면 수
+    planes: usize,
+    // (타일 번호, 평면마다 타일 영역을 이어 붙인 값)
+    tiles: Vec<(usize, Vec<f32>)>,
+}
+
+impl Delta {
+    fn bytes(&self) -> usize {
+        std::mem::size_of::<Delta>()
+            + self.palette.len() * 4
+            + self
+                .tiles
+                .iter()
+                .map(|(_, data)| std::mem::size_of::<(usize, Vec<f32>)>() + data.len() * 4)
+                .sum::<usize>()
+    }
+}
+
+// 다시하기 항목: 되돌리기 직전 체크포인트로 shadow를 되돌리는 차이(base)와 되돌리기 직전 실제 상태로 가는 차이(forward)
+// 더 이전 체크포인트가 없었으면 base는 None이고 shadow가 그대로 그 체크포인트다.
+struct RedoEntry {
+    base: Option<Delta>,
+    forward: Delta,
+}
+
+pub(crate) struct History {
+    width: usize,
+    height: usize,
+    cols: usize,
+    budget: usize,
+    shadow: Option<Shadow>,
+    // shadow가 되돌릴 수 있는 체크포인트인지 (모두 되돌리면 사본은 남지만 더 갈 곳이 없다)
+    anchored: bool,
+    // undo[k]는 체크포인트 k+1을 체크포인트 k로 바꾼다 (마지막 항목이 shadow의 바로 이전)
+    undo: Vec<Delta>,
+    redo: Vec<RedoEntry>,
+    // undo와 redo의 차이가 차지하는 바이트
+    bytes: usize,
+}
+
+impl History {
+    pub(crate) fn new(width: usize, height: usize, budget: usize) -> History {
+        History {
+            width,
+            height,
+            cols: width.div_ceil(TILE),
+            budget,
+            shadow: None,
+            anchored: false,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        self.anchored
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // 타일 t의 셀 범위 [x0, x1) x [y0, y1)
+    fn rect(&self, t: usize) -> (usize, usize, usize, usize) {
+        let (tx, ty) = (t % self.cols, t / self.cols);
+        let x1 = ((tx + 1) * TILE).min(self.width);
+        let y1 = ((ty + 1) * TILE).min(self.height);
+        (tx * TILE, ty * TILE, x1, y1)
+    }
+
+    // 평면 안의 타일 t 영역을 행마다 (시작, 끝) 인덱스로 돌려준다.
+    // 타일은 왼쪽/위 경계면을 갖고, 캔버스 오른쪽/아래 가장자리 경계면은 끝 타일이 갖는다 (타일끼리 겹치지 않는다).
+    fn rows(&self, grid: Grid, t: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (x0, y0, x1, y1) = self.rect(t);
+        let edge = |end: usize, size: usize| if end == size { end + 1 } else { end };
+        let (stride, x1, y1) = match grid {
+            Grid::Cell => (self.width, x1, y1),
+            Grid::U => (self.width + 1, edge(x1, self.width), y1),
+            Grid::V => (self.width, x1, edge(y1, self.height)),
+        };
+        (y0..y1).map(move |y| (y * stride + x0, y * stride + x1))
+    }
+
+    // 바뀌었을 수 있는 타일에 오른쪽과 아래 이웃을 더한다.
+    // 돌린 타일은 오른쪽/아래 경계면에도 쓰는데, 그 경계면은 이웃 타일 몫이다.
+    fn around(&self, edited: &[usize]) -> Vec<usize> {
+        let rows = self.height.div_ceil(TILE);
+        let mut on = vec![false; self.cols * rows];
+        for &t in edited {
+            let (tx, ty) = (t % self.cols, t / self.cols);
+            on[t] = true;
+            if tx + 1 < self.cols {
+                on[t + 1] = true;
+            }
+            if ty + 1 < rows {
+                on[t + self.cols] = true;
+            }
+        }
+        (0..on.len()).filter(|&t| on[t]).collect()
+    }
+
+    fn same(&self, a: &mut Fields, b: &mut Fields, t: usize) -> bool {
+        a.planes()
+            .into_iter()
+            .zip(b.planes())
+            .all(|((a, grid), (b, _))| {
+                self.rows(grid, t).all(|(i0, i1)| {
+                    a[i0..i1]
+                        .iter()
+                        .zip(&b[i0..i1])
+                        .all(|(x, y)| x.to_bits() == y.to_bits())
+                })
+            })
+    }
+
+    fn read(&self, f: &mut Fields, t: usize) -> Vec<f32> {
+        let mut out = Vec::new();
+        for (plane, grid) in f.planes() {
+            for (i0, i1) in self.rows(grid, t) {
+                out.extend_from_slice(&plane[i0..i1]);
+            }
+        }
+        out
+    }
+
+    fn swap(&self, f: &mut Fields, t: usize, data: &mut [f32]) {
+        let mut at = 0;
+        for (plane, grid) in f.planes() {
+            for (i0, i1) in self.rows(grid, t) {
+                plane[i0..i1].swap_with_slice(&mut data[at..at + i1 - i0]);
+                at += i1 - i0;
+            }
+        }
+    }
+
+    // tiles 중 src와 다른 dst 타일을 src 값으로 바꾸고 팔레트도 맞춘다. 바꾸기 전 dst 값을 차이로 돌려준다.
+    fn sync(&self, src: &mut Fields, dst: &mut Fields, tiles: &[usize]) -> Delta {
+        let total = self.width * self.height;
+        let planes = src.g.len().max(dst.g.len());
+        src.widen(planes, total);
+        dst.widen(planes, total);
+        let mut delta = Delta {
+            palette: dst.palette.clone(),
+            planes,
+            tiles: Vec::new(),
+        };
+        for &t in tiles {
+            if !self.same(src, dst, t) {
+                let mut data = self.read(src, t);
+                self.swap(dst, t, &mut data);
+                delta.tiles.push((t, data));
+            }
+        }
+        dst.palette.clone_from(src.palette);
+        src.fit();
+        dst.fit();
+        delta
+    }
+
+    // 차이를 f에 적용하고, 바꾸기 전 f의 값을 차이에 담는다
+    fn apply(&self, delta: &mut Delta, f: &mut Fields) {
+        f.widen(delta.planes, self.width * self.height);
+        for (t, data) in &mut delta.tiles {
+            self.swap(f, *t, data);
+        }
+        std::mem::swap(&mut delta.palette, f.palette);
+        f.fit();
+    }
+
+    // 예산을 넘으면 가장 오래된 되돌리기부터 버린다
+    fn trim(&mut self) {
+        while self.bytes > self.budget && !self.undo.is_empty() {
+            self.bytes -= self.undo.remove(0).bytes();
+        }
+    }
+
+    // 붓 입력 직전 상태를 체크포인트로 남긴다. edited: 지난 체크포인트 뒤 바뀌었을 수 있는 타일
+    pub(crate) fn record(&mut self, live: &mut Fields, edited: &[usize]) {
+        let Some(mut shadow) = self.shadow.take() else {
+            self.shadow = Some(Shadow::of(live));
+            self.anchored = true;
+            return;
+        };
+        let delta = self.sync(live, &mut shadow.fields(), &self.around(edited));
+        self.shadow = Some(shadow);
+        for entry in self.redo.drain(..) {
+            self.bytes -= entry.forward.bytes() + entry.base.as_ref().map_or(0, Delta::bytes);
+        }
+        if self.anchored {
+            self.bytes += delta.bytes();
+            self.undo.push(delta);
+            self.trim();
+        }
+        self.anchored = true;
+    }
+
+    // live를 마지막 체크포인트로 되돌린다. 값이 바뀌었거나 체크포인트와 달라진 타일을 돌려준다.
+    pub(crate) fn undo(&mut self, live: &mut Fields, edited: &[usize]) -> Option<Vec<usize>> {
+        if !self.anchored {
+            return None;
+        }
+        let mut shadow = self.shadow.take()?;
+        let mut sh = shadow.fields();
+        let forward = self.sync(&mut sh, live, &self.around(edited));
+        let mut touched: Vec<usize> = forward.tiles.iter().map(|(t, _)| *t).collect();
+        let base = match self.undo.pop() {
+            Some(mut delta) => {
+                self.bytes -= delta.bytes();
+                self.apply(&mut delta, &mut sh);
+                self.bytes += delta.bytes();
+                touched.extend(delta.tiles.iter().map(|(t, _)| *t));
+                Some(delta)
+            }
+            None => {
+                self.anchored = false;
+                None
+            }
+        };
+        self.shadow = Some(shadow);
+        self.bytes += forward.bytes();
+        self.redo.push(RedoEntry { base, forward });
+        self.trim();
+        Some(touched)
+    }
+
+    // 마지막 되돌리기 직전 상태로 간다. 되돌린 뒤의 변화(step 등)는 버린다.
+    pub(crate) fn redo(&mut self, live: &mut Fields, edited: &[usize]) -> Option<Vec<usize>> {
+        let entry = self.redo.pop()?;
+        self.bytes -= entry.forward.bytes() + entry.base.as_ref().map_or(0, Delta::bytes);
+        let mut shadow = self.shadow.take()?;
+        let mut sh = shadow.fields();
+        let reverted = self.sync(&mut sh, live, &self.around(edited));
+        let mut touched: Vec<usize> = reverted.tiles.iter().map(|(t, _)| *t).collect();
+        match entry.base {
+            Some(mut delta) => {
+                self.apply(&mut delta, &mut sh);
+                let tiles: Vec<usize> = delta.tiles.iter().map(|(t, _)| *t).collect();
+                self.sync(&mut sh, live, &tiles);
+                touched.extend(tiles);
+                self.bytes += delta.bytes();
+                self.undo.push(delta);
+            }
+            None => self.anchored = true,
+        }
+        let mut forward = entry.forward;
+        self.apply(&mut forward, live);
+        touched.extend(forward.tiles.iter().map(|(t, _)| *t));
+        self.shadow = Some(shadow);
+        Some(touched)
+    }
+}
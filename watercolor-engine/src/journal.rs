@@ -0,0 +1,581 @@
+// 붓 기록(저널)과 결정적 재생
+// 기록을 시작한 순간의 전체 상태(save_state)를 바탕으로, 그 뒤에 들어온 붓 호출·설정 변경과
+// 각 호출이 몇 번째 step()에서 적용됐는지를 남긴다. 시뮬레이션이 결정적이므로
+// 같은 바탕 상태에서 같은 순서로 다시 호출하면 픽셀까지 똑같이 나온다.
+//
+// 형식: "WCJN" | 버전 u16 | 예약 u16 | 바탕 상태 (u32 길이 + save_state 바이트)
+//       | 전체 step 수 u32 | 항목 수 u32 | 항목들
+// 항목: step u32 | 종류 u8 | 내용 (0: 호출 = 도구 u8 + 인자 수 u32 + f32 인자, 1: 안료, 2: 종이 텍스처,
+//       3: 붓끝 이미지)
+// v1은 인자 수를 u8로 적었다.
+
+use crate::pigment::Pigment;
+use crate::spline::SAMPLE;
+use crate::state::{self, Reader, Writer};
+use crate::WatercolorEngine;
+
+const MAGIC: &[u8; 4] = b"WCJN";
+const FORMAT_VERSION: u16 = 2;
+
+const KIND_CALL: u8 = 0;
+const KIND_PIGMENT: u8 = 1;
+const KIND_PAPER: u8 = 2;
+const KIND_TIP: u8 = 3;
+
+// 기록되는 공개 API. 정수 인자(좌표, 안료 id)도 f32로 담는다 (2^24 미만이면 정확하다).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Tool {
+    Brush = 1,
+    BrushStroke,
+    BackgroundBrush,
+    BackgroundBrushStroke,
+    FadeBrush,
+    FadeBrushStroke,
+    BlendBrush,
+    BlendBrushStroke,
+    SilhouetteBlendBrush,
+    SilhouetteBlendBrushStroke,
+    WaterBrush,
+    WaterBrushStroke,
+    BeginStroke,
+    EndStroke,
+    Undo,
+    Redo,
+    Reset,
+    SetPhysics,
+    SetCapillaryProps,
+    SetBloomStrength,
+    SetEdgeDarkening,
+    SetPigmentProps,
+    SetShowTexture,
+    SetDebugView,
+    SetSilhouetteControls,
+    PenBrush,
+    PenStroke,
+    SplineStroke,
+    LoadBrush,
+    RinseBrush,
+    LoadedBrush,
+    LoadedBrushStroke,
+    SetDrybrush,
+    SetBrushTip,
+}
+
+const TOOLS: [Tool; 34] = [
+    Tool::Brush,
+    Tool::BrushStroke,
+    Tool::BackgroundBrush,
+    Tool::BackgroundBrushStroke,
+    Tool::FadeBrush,
+    Tool::FadeBrushStroke,
+    Tool::BlendBrush,
+    Tool::BlendBrushStroke,
+    Tool::SilhouetteBlendBrush,
+    Tool::SilhouetteBlendBrushStroke,
+    Tool::WaterBrush,
+    Tool::WaterBrushStroke,
+    Tool::BeginStroke,
+    Tool::EndStroke,
+    Tool::Undo,
+    Tool::Redo,
+    Tool::Reset,
+    Tool::SetPhysics,
+    Tool::SetCapillaryProps,
+    Tool::SetBloomStrength,
+    Tool::SetEdgeDarkening,
+    Tool::SetPigmentProps,
+    Tool::SetShowTexture,
+    Tool::SetDebugView,
+    Tool::SetSilhouetteControls,
+    Tool::PenBrush,
+    Tool::PenStroke,
+    Tool::SplineStroke,
+    Tool::LoadBrush,
+    Tool::RinseBrush,
+    Tool::LoadedBrush,
+    Tool::LoadedBrushStroke,
+    Tool::SetDrybrush,
+    Tool::SetBrushTip,
+];
+
+impl Tool {
+    fn from_u8(v: u8) -> Option<Tool> {
+        TOOLS.iter().copied().find(|&t| t as u8 == v)
+    }
+
+    // 도구별 인자 수 (곡선 획은 고정 인자 수, 그 뒤로 표본이 이어진다)
+    fn arity(self) -> usize {
+        match self {
+            Tool::Brush => 8,
+            Tool::BrushStroke => 9,
+            Tool::BackgroundBrush => 6,
+            Tool::BackgroundBrushStroke => 8,
+            Tool::FadeBrush | Tool::BlendBrush | Tool::SilhouetteBlendBrush => 6,
+            Tool::FadeBrushStroke | Tool::BlendBrushStroke | Tool::SilhouetteBlendBrushStroke => 7,
+            Tool::WaterBrush => 7,
+            Tool::WaterBrushStroke => 8,
+            Tool::BeginStroke | Tool::EndStroke | Tool::Undo | Tool::Redo | Tool::Reset => 0,
+            Tool::SetPhysics => 5,
+            Tool::SetCapillaryProps | Tool::SetPigmentProps | Tool::SetSilhouetteControls => 2,
+            Tool::SetBloomStrength
+            | Tool::SetEdgeDarkening
+            | Tool::SetShowTexture
+            | Tool::SetDebugView
+            | Tool::SetDrybrush
+            | Tool::SetBrushTip => 1,
+            Tool::PenBrush => 10,
+            Tool::PenStroke => 16,
+            Tool::SplineStroke => 4,
+            Tool::LoadBrush => 3,
+            Tool::RinseBrush => 0,
+            Tool::LoadedBrush => 5,
+            Tool::LoadedBrushStroke => 6,
+        }
+    }
+
+    fn accepts(self, n: usize) -> bool {
+        match self {
+            Tool::SplineStroke => n >= self.arity() && (n - self.arity()).is_multiple_of(SAMPLE),
+            _ => n == self.arity(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum Entry {
+    Call(Tool, Vec<f32>),
+    Pigment(Pigment),
+    Paper {
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+    Tip {
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+}
+
+#[derive(Clone)]
+pub(crate) struct Record {
+    pub step: u32,
+    pub entry: Entry,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Journal {
+    recording: bool,
+    // 붓 호출 안에서 다시 불리는 공개 API(획 -> 점)는 기록하지 않는다
+    busy: bool,
+    base: Vec<u8>,
+    steps: u32,
+    records: Vec<Record>,
+}
+
+impl Journal {
+    pub(crate) fn start(&mut self, base: Vec<u8>) {
+        self.recording = true;
+        self.busy = false;
+        self.base = base;
+        self.steps = 0;
+        self.records.clear();
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    // start() 이후 (멈췄더라도) 내보낼 기록이 있는지
+    pub(crate) fn is_started(&self) -> bool {
+        !self.base.is_empty()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub(crate) fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    pub(crate) fn tick(&mut self) {
+        if self.recording {
+            self.steps += 1;
+        }
+    }
+
+    // 호출을 기록하고 그 호출이 끝날 때까지 중첩 호출 기록을 막는다. leave()와 짝을 이룬다.
+    pub(crate) fn enter(&mut self, entry: impl FnOnce() -> Entry) -> bool {
+        if !self.recording || self.busy {
+            return false;
+        }
+        self.records.push(Record {
+            step: self.steps,
+            entry: entry(),
+        });
+        self.busy = true;
+        true
+    }
+
+    pub(crate) fn leave(&mut self, entered: bool) {
+        if entered {
+            self.busy = false;
+        }
+    }
+
+    pub(crate) fn record(&mut self, entry: impl FnOnce() -> Entry) {
+        let entered = self.enter(entry);
+        self.leave(entered);
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut w = Writer { buf: Vec::new() };
+        w.buf.extend_from_slice(MAGIC);
+        w.u16(FORMAT_VERSION);
+        w.u16(0);
+        w.bytes(&self.base);
+        w.u32(self.steps);
+        w.u32(self.records.len() as u32);
+        for rec in &self.records {
+            w.u32(rec.step);
+            match &rec.entry {
+                Entry::Call(tool, args) => {
+                    w.u8(KIND_CALL);
+                    w.u8(*tool as u8);
+                    w.u32(args.len() as u32);
+                    for &a in args {
+                        w.f32(a);
+                    }
+                }
+                Entry::Pigment(pig) => {
+                    w.u8(KIND_PIGMENT);
+                    state::write_pigment(&mut w, pig);
+                }
+                Entry::Paper {
+                    data,
+                    width,
+                    height,
+                } => {
+                    w.u8(KIND_PAPER);
+                    w.u32(*width);
+                    w.u32(*height);
+                    w.bytes(data);
+                }
+                Entry::Tip {
+                    data,
+                    width,
+                    height,
+                } => {
+                    w.u8(KIND_TIP);
+                    w.u32(*width);
+                    w.u32(*height);
+                    w.bytes(data);
+                }
+            }
+        }
+        w.buf
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Journal, String> {
+        let mut r = Reader::new(data);
+        if r.take(4)? != MAGIC {
+            return Err("붓 기록 파일이 아닙니다".to_string());
+        }
+        let version = r.u16()?;
+        if version > FORMAT_VERSION {
+            return Err(format!(
+                "더 새로운 버전의 붓 기록입니다 (v{} > v{})",
+                version, FORMAT_VERSION
+            ));
+        }
+        r.u16()?;
+        let base = r.bytes()?.to_vec();
+        let steps = r.u32()?;
+        let count = r.u32()?;
+        let mut records = Vec::new();
+        let mut last_step = 0;
+        for _ in 0..count {
+            let step = r.u32()?;
+            if step < last_step || step > steps {
+                return Err("붓 기록의 step 순서가 맞지 않습니다".to_string());
+            }
+            last_step = step;
+            let entry = match r.u8()? {
+                KIND_CALL => {
+                    let tool = Tool::from_u8(r.u8()?)
+                        .ok_or_else(|| "알 수 없는 도구입니다".to_string())?;
+                    // v1은 인자 수를 u8로 적었다
+                    let n = if version < 2 {
+                        r.u8()? as usize
+                    } else {
+                        r.u32()? as usize
+                    };
+                    if !tool.accepts(n) {
+                        return Err(format!("{:?} 인자 수가 맞지 않습니다 ({})", tool, n));
+                    }
+                    // 손상된 인자 수로 거대한 버퍼를 잡지 않도록 남은 바이트부터 본다
+                    if n > r.remaining() / 4 {
+                        return Err("붓 기록 데이터가 중간에 끊겼습니다".to_string());
+                    }
+                    let mut args = Vec::with_capacity(n);
+                    for _ in 0..n {
+                        args.push(r.f32()?);
+                    }
+                    Entry::Call(tool, args)
+                }
+                KIND_PIGMENT => Entry::Pigment(state::read_pigment(&mut r)?),
+                KIND_PAPER => {
+                    let width = r.u32()?;
+                    let height = r.u32()?;
+                    let data = r.bytes()?.to_vec();
+                    Entry::Paper {
+                        data,
+                        width,
+                        height,
+                    }
+                }
+                KIND_TIP => {
+                    let width = r.u32()?;
+                    let height = r.u32()?;
+                    let data = r.bytes()?.to_vec();
+                    Entry::Tip {
+                        data,
+                        width,
+                        height,
+                    }
+                }
+                _ => return Err("알 수 없는 기록 항목입니다".to_string()),
+            };
+            records.push(Record { step, entry });
+        }
+        Ok(Journal {
+            recording: false,
+            busy: false,
+            base,
+            steps,
+            records,
+        })
+    }
+}
+
+impl WatercolorEngine {
+    fn apply_call(&mut self, tool: Tool, a: &[f32]) {
+        let i = |k: usize| a[k] as i32;
+        match tool {
+            Tool::Brush => self.apply_brush(i(0), i(1), a[2], a[3], a[4], a[5] as u32, a[6], a[7]),
+            Tool::BrushStroke => {
+                self.apply_brush_stroke(i(0), i(1), i(2), i(3), a[4], a[5], a[6], a[7] as u32, a[8])
+            }
+            Tool::BackgroundBrush => {
+                self.apply_background_brush(i(0), i(1), a[2], a[3], a[4], a[5] as u32)
+            }
+            Tool::BackgroundBrushStroke => self.apply_background_brush_stroke(
+                i(0),
+                i(1),
+                i(2),
+                i(3),
+                a[4],
+                a[5],
+                a[6],
+                a[7] as u32,
+            ),
+            Tool::FadeBrush => self.apply_fade_brush(i(0), i(1), a[2], a[3], a[4], a[5]),
+            Tool::FadeBrushStroke => {
+                self.apply_fade_brush_stroke(i(0), i(1), i(2), i(3), a[4], a[5], a[6])
+            }
+            Tool::BlendBrush => self.apply_blend_brush(i(0), i(1), a[2], a[3], a[4], a[5]),
+            Tool::BlendBrushStroke => {
+                self.apply_blend_brush_stroke(i(0), i(1), i(2), i(3), a[4], a[5], a[6])
+            }
+            Tool::SilhouetteBlendBrush => {
+                self.apply_silhouette_blend_brush(i(0), i(1), a[2], a[3], a[4], a[5])
+            }
+            Tool::SilhouetteBlendBrushStroke => {
+                self.apply_silhouette_blend_brush_stroke(i(0), i(1), i(2), i(3), a[4], a[5], a[6])
+            }
+            Tool::WaterBrush => self.apply_water_brush(i(0), i(1), a[2], a[3], a[4], a[5], a[6]),
+            Tool::WaterBrushStroke => {
+                self.apply_water_brush_stroke(i(0), i(1), i(2), i(3), a[4], a[5], a[6], a[7])
+            }
+            Tool::BeginStroke => self.begin_stroke(),
+            Tool::EndStroke => self.end_stroke(),
+            Tool::Undo => {
+                self.undo();
+            }
+            Tool::Redo => {
+                self.redo();
+            }
+            Tool::Reset => self.reset(),
+            Tool::SetPhysics => self.set_physics(a[0], a[1], a[2], a[3], a[4] as u32),
+            Tool::SetCapillaryProps => self.set_capillary_props(a[0], a[1]),
+            Tool::SetBloomStrength => self.set_bloom_strength(a[0]),
+            Tool::SetEdgeDarkening => self.set_edge_darkening(a[0]),
+            Tool::SetPigmentProps => self.set_pigment_props(a[0], a[1]),
+            Tool::SetShowTexture => self.set_show_texture(a[0] != 0.0),
+            Tool::SetDebugView => self.set_debug_view(a[0] as u32),
+            Tool::SetSilhouetteControls => self.set_silhouette_controls(a[0], a[1]),
+            Tool::PenBrush => self.apply_pen_brush(
+                i(0),
+                i(1),
+                a[2],
+                a[3],
+                a[4],
+                a[5] as u32,
+                a[6],
+                a[7],
+                a[8],
+                a[9],
+            ),
+            Tool::PenStroke => self.apply_pen_stroke(
+                i(0),
+                i(1),
+                i(2),
+                i(3),
+                a[4],
+                a[5],
+                a[6],
+                a[7] as u32,
+                a[8],
+                a[9],
+                a[10],
+                a[11],
+                a[12],
+                a[13],
+                a[14],
+                a[15],
+            ),
+            Tool::SplineStroke => self.apply_spline_stroke(&a[4..], a[0], a[1], a[2], a[3] as u32),
+            Tool::LoadBrush => self.load_brush(a[0], a[1], a[2] as u32),
+            Tool::RinseBrush => self.rinse_brush(),
+            Tool::SetDrybrush => self.set_drybrush(a[0] != 0.0),
+            Tool::SetBrushTip => self.set_brush_tip(a[0] as u32),
+            Tool::LoadedBrush => self.apply_loaded_brush(i(0), i(1), a[2], a[3], a[4]),
+            Tool::LoadedBrushStroke => {
+                self.apply_loaded_brush_stroke(i(0), i(1), i(2), i(3), a[4], a[5])
+            }
+        }
+    }
+}
+
+// 배율 재생에서 위치(셀 좌표)와 붓 크기 인자를 키운다.
+// (위치 인자 수, 붓 크기 인자 위치) — 위치는 항상 앞쪽에 온다.
+fn spatial_args(tool: Tool) -> Option<(usize, usize)> {
+    match tool {
+        Tool::Brush
+        | Tool::BackgroundBrush
+        | Tool::FadeBrush
+        | Tool::BlendBrush
+        | Tool::SilhouetteBlendBrush
+        | Tool::WaterBrush
+        | Tool::PenBrush
+        | Tool::LoadedBrush => Some((2, 2)),
+        Tool::BrushStroke
+        | Tool::BackgroundBrushStroke
+        | Tool::FadeBrushStroke
+        | Tool::BlendBrushStroke
+        | Tool::SilhouetteBlendBrushStroke
+        | Tool::WaterBrushStroke
+        | Tool::PenStroke
+        | Tool::LoadedBrushStroke => Some((4, 4)),
+        _ => None,
+    }
+}
+
+fn scale_args(tool: Tool, args: &[f32], k: u32) -> Vec<f32> {
+    let mut out = args.to_vec();
+    if tool == Tool::SplineStroke {
+        let k = k as f32;
+        out[0] *= k;
+        // 실수 좌표는 원래 위치를 그대로 옮기고, 속도가 같도록 시각도 늘린다
+        for s in out[4..].chunks_exact_mut(SAMPLE) {
+            s[0] = (s[0] + 0.5) * k - 0.5;
+            s[1] = (s[1] + 0.5) * k - 0.5;
+            s[3] *= k;
+        }
+        return out;
+    }
+    if let Some((coords, size)) = spatial_args(tool) {
+        let k = k as f32;
+        // 원래 셀 중심이 큰 캔버스에서 가리키는 셀
+        for a in &mut out[..coords] {
+            *a = ((*a + 0.5) * k).floor();
+        }
+        out[size] *= k;
+    }
+    out
+}
+
+// 바탕 상태에서 시작해 기록된 호출과 step()을 같은 순서로 다시 실행한 엔진을 만든다.
+// scale > 1이면 바탕 상태를 scale배로 키우고 좌표와 붓 크기를 늘린다.
+// step 수는 그대로이며, 엔진의 scale 값에 따라 step 안에서 dt/scale 하위 단계로 나누어 진행한다.
+pub(crate) fn replay(data: &[u8], scale: u32) -> Result<WatercolorEngine, String> {
+    let journal = Journal::decode(data)?;
+    let mut e = state::load(&journal.base)?;
+    if scale > 1 {
+        let cells = e.width as u64 * e.height as u64 * scale as u64 * scale as u64;
+        if cells > state::MAX_CELLS {
+            return Err(format!("배율 {}배 캔버스가 너무 큽니다", scale));
+        }
+        e = e.upscaled(scale as usize);
+    }
+    let mut step = 0;
+    let mut run_to = |e: &mut WatercolorEngine, target: u32| {
+        while step < target {
+            e.step();
+            step += 1;
+        }
+    };
+    for rec in &journal.records {
+        run_to(&mut e, rec.step);
+        match &rec.entry {
+            Entry::Call(tool, args) if scale > 1 => {
+                e.apply_call(*tool, &scale_args(*tool, args, scale))
+            }
+            Entry::Call(tool, args) => e.apply_call(*tool, args),
+            Entry::Pigment(pig) => {
+                e.pigments.add(pig.clone());
+            }
+            Entry::Paper {
+                data,
+                width,
+                height,
+            } => e.load_paper_texture(data, *width, *height),
+            Entry::Tip {
+                data,
+                width,
+                height,
+            } => e.load_brush_tip(data, *width, *height),
+        }
+    }
+    run_to(&mut e, journal.steps);
+    Ok(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_journal_is_rejected() {
+        let mut e = WatercolorEngine::new(16, 16);
+        e.start_journal();
+        let blue = e.find_pigment("ultramarine") as u32;
+        e.apply_brush_stroke(2, 2, 12, 10, 3.0, 0.9, 0.8, blue, 0.0);
+        e.step();
+        e.undo();
+        let data = e.export_journal();
+        assert!(Journal::decode(&data).is_ok());
+        for cut in 0..data.len() {
+            assert!(
+                Journal::decode(&data[..cut]).is_err(),
+                "{}바이트에서 잘림",
+                cut
+            );
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// Kubelka-Munk 광학 모델
+// 안료층을 흡수(K)·산란(S) 계수를 가진 얇은 막으로 보고 반사율/투과율을 계산한 뒤
+// 종이 위에 층을 차례로 합성한다. (Curtis et al. 1997, "Computer-Generated Watercolor")
+
+use crate::simd::F32x4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KsCoeffs {
+    pub k: [f32; 3],
+    pub s: [f32; 3],
+}
+
+impl KsCoeffs {
+    pub const ZERO: KsCoeffs = KsCoeffs {
+        k: [0.0; 3],
+        s: [0.0; 3],
+    };
+}
+
+// 단일 채널의 얇은 막 반사율 R, 투과율 T
+// k, s는 이미 농도(두께)가 곱해진 값이다.
+pub fn layer_rt(k: f32, s: f32) -> (f32, f32) {
+    if k <= 1e-6 && s <= 1e-6 {
+        return (0.0, 1.0);
+    }
+    if s <= 1e-5 {
+        // 산란이 없으면 Beer-Lambert 흡수만 남는다.
+        return (0.0, (-k).exp());
+    }
+    let a = 1.0 + k / s;
+    let b = (a * a - 1.0).max(0.0).sqrt();
+    // sinh/cosh 오버플로 방지: bS가 충분히 크면 무한 두께 층과 같다.
+    let bs = (b * s).min(20.0);
+    let sh = bs.sinh();
+    let ch = bs.cosh();
+    let c = a * sh + b * ch;
+    if c <= 1e-12 {
+        return (0.0, 1.0);
+    }
+    (sh / c, b / c)
+}
+
+// 위층(r1, t1)을 아래층(r2, t2) 위에 올렸을 때의 합성 반사율/투과율
+pub fn composite(r1: f32, t1: f32, r2: f32, t2: f32) -> (f32, f32) {
+    let denom = (1.0 - r1 * r2).max(1e-6);
+    (r1 + t1 * t1 * r2 / denom, t1 * t2 / denom)
+}
+
+// 농도 가중 K/S 혼합 (선형 합)
+pub fn mix(out: &mut KsCoeffs, coeffs: &KsCoeffs, amount: f32) {
+    for c in 0..3 {
+        out.k[c] += coeffs.k[c] * amount;
+        out.s[c] += coeffs.s[c] * amount;
+    }
+}
+
+// layer_rt의 4칸 묶음판 (render 안쪽 루프용). sinh/cosh는 exp 하나로 만들고,
+// bS가 작을 때는 e^x - e^-x의 자리 손실을 피하려고 급수를 쓴다.
+pub(crate) fn layer_rt4(k: F32x4, s: F32x4) -> (F32x4, F32x4) {
+    let zero = F32x4::splat(0.0);
+    let one = F32x4::splat(1.0);
+    let half = F32x4::splat(0.5);
+    let empty = k.le(F32x4::splat(1e-6)).and(s.le(F32x4::splat(1e-6)));
+    let beer = s.le(F32x4::splat(1e-5));
+
+    let a = one.add(k.div(s));
+    let b = a.mul(a).sub(one).max(zero).sqrt();
+    let bs = b.mul(s).min(F32x4::splat(20.0));
+    let e = bs.exp();
+    let inv = one.div(e);
+    let ch = e.add(inv).mul(half);
+    let x2 = bs.mul(bs);
+    let series = bs.mul(
+        one.add(
+            x2.mul(
+                F32x4::splat(1.0 / 6.0)
+                    .add(x2.mul(F32x4::splat(1.0 / 120.0).add(x2.mul(F32x4::splat(1.0 / 5040.0))))),
+            ),
+        ),
+    );
+    let sh = bs.lt(one).select(series, e.sub(inv).mul(half));
+    let c = a.mul(sh).add(b.mul(ch));
+    let flat = c.le(F32x4::splat(1e-12));
+
+    let t_beer = zero.sub(k).exp();
+    let r = empty.or(beer).or(flat).select(zero, sh.div(c));
+    let t = empty.select(one, beer.select(t_beer, flat.select(one, b.div(c))));
+    (r, t)
+}
+
+pub(crate) fn composite4(r1: F32x4, t1: F32x4, r2: F32x4, t2: F32x4) -> (F32x4, F32x4) {
+    let denom = F32x4::splat(1.0).sub(r1.mul(r2)).max(F32x4::splat(1e-6));
+    (r1.add(t1.mul(t1).mul(r2).div(denom)), t1.mul(t2).div(denom))
+}
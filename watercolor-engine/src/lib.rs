@@ -1,3 +1,30 @@
+
+mod brush;
+mod history;
+mod journal;
+pub mod km;
+mod pen;
+pub mod pigment;
+pub mod png;
+mod resample;
+mod rows;
+mod simd;
+mod spline;
+mod state;
+mod tiles;
+mod tip;
+
+use brush::Brush;
+use history::{Fields, History};
+use journal::{Entry, Journal, Tool};
+use pen::PenSample;
+use pigment::{Pigment, PigmentLibrary};
+use simd::{F32x4, LANES};
+use spline::SAMPLE;
+use tiles::{Tiles, TILE};
+use tip::Tip;
+
+pub use state::MAX_CELLS;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -6,20 +33,36 @@ pub struct WatercolorEngine {
     height: usize,
     total: usize,
 
-    // 유체 필드
+    // 유체 필드 (스태거드 MAC 격자: 속도는 셀 경계면, 수위/압력은 셀 중심)
     h: Vec<f32>, // 수위
-    u: Vec<f32>, // x 속도
-    v: Vec<f32>, // y 속도
+    u: Vec<f32>, // x 속도, 세로 경계면 (width + 1) x height
+    v: Vec<f32>, // y 속도, 가로 경계면 width x (height + 1)
     p: Vec<f32>, // 압력
     mask: Vec<f32>,
 
-    // RGB 안료
-    gr: Vec<f32>,
-    gg: Vec<f32>,
-    gb: Vec<f32>,
-    dr: Vec<f32>,
-    dg: Vec<f32>,
-    db: Vec<f32>,
+    // 모세관층: 종이 섬유에 스며든 물 (포화도)
+    s: Vec<f32>,
+    capacity: Vec<f32>, // 섬유가 머금을 수 있는 최대 물 (paper_h에서 계산)
+
+    // 젖은 영역 마스크와 가장자리까지의 거리 (step마다 갱신)
+    wet_mask: Vec<f32>,
+    edge_dist: Vec<f32>,
+
+    // 타일 활동 지도: step은 깨어 있는 타일 근처만 계산한다
+    tiles: Tiles,
+    // step 안에서 이전 값 스냅샷과 임시 평면으로 돌려 쓰는 버퍼 (Tiles::copy)
+    scratch: Vec<Vec<f32>>,
+
+    // 팔레트 슬롯별 안료 평면 (g: 물에 떠 있는 안료, d: 종이에 침착된 안료)
+    g: Vec<Vec<f32>>,
+    d: Vec<Vec<f32>>,
+    pigments: PigmentLibrary,
+    palette: Vec<u32>,
+    // 팔레트가 찼을 때 다시 쓸 수 있는 (안료가 거의 남지 않은) 슬롯. None이면 아직 훑지 않았다.
+    // step, 되돌리기, 초기화 뒤에 비우고 필요할 때 한 번만 다시 훑는다.
+    free_slots: Option<Vec<usize>>,
+    // 물감을 머금은 붓 (apply_loaded_brush)
+    brush: Brush,
 
     // 종이 텍스처
     paper_h: Vec<f32>,
@@ -41,9 +84,111 @@ pub struct WatercolorEngine {
     adhesion: f32,
     granularity: f32,
 
+    // 모세관층
+    absorption: f32,
+    capillary_diffusion: f32,
+    bloom_strength: f32,
+    edge_darkening: f32,
+
+    // 해상도 배율: 원래 캔버스 한 셀이 차지하는 셀 수 (replay_scaled)
+    // step 한 번은 원래 step의 1/scale 시간이며, 셀 크기에 묶인 물리량은 이 값으로 환산한다.
+    scale: f32,
+
+    // 진단: 마지막 step() 이후 젖은 영역의 잔여 발산 (RMS)
+    divergence_residual: f32,
+
+    // 되돌리기 기록 (begin_stroke ~ end_stroke 사이는 하나의 동작으로 묶인다)
+    history: History,
+    stroke_open: bool,
+    // 붓 기록 (start_journal 이후의 호출과 step 수)
+    journal: Journal,
+
     show_texture: bool,
+    debug_view: u32,
     silhouette_strength: f32,
     edge_bleed_strength: f32,
+    // 마른 붓 (set_drybrush): 물이 적은 붓 자국은 종이 결의 봉우리에만 닿는다
+    drybrush: bool,
+    // 붓끝 모양 (set_brush_tip / load_brush_tip). None이면 타원 가우시안
+    tip: Option<Tip>,
+}
+
+// 동시에 캔버스에 올릴 수 있는 안료 수
+const MAX_PIGMENTS: usize = 8;
+// 슬롯의 g, d가 모두 이보다 작으면 캔버스에서 사라진 것으로 보고 다른 안료에 내준다.
+// 흐름과 증발이 남기는 찌꺼기는 정확히 0이 되지 않는다.
+const SLOT_EPS: f32 = 1e-4;
+const PAPER_REFLECTANCE: f32 = 1.0;
+// 안료 농도 1당 KM 박막 두께 (유체 이동이 안료를 보존하므로 붓 한 번의 농도가 누적된다)
+const PIGMENT_THICKNESS: f32 = 0.25;
+// 부유 안료층은 아직 얇게 퍼져 있으므로 유효 두께를 줄여 합성
+const SUSPENDED_THICKNESS: f32 = 0.4;
+// 이 수위 이하의 셀은 마른 종이로 보고 경계면 속도를 막는다
+const WET_THRESHOLD: f32 = 0.001;
+// CFL 조건을 맞추기 위한 step 당 최대 하위 스텝 수
+const MAX_SUBSTEPS: usize = 8;
+const PRESSURE_TOLERANCE: f32 = 1e-4;
+// 모세관층: 포화도가 이 값을 넘으면 젖은 영역으로 본다
+const DAMP_THRESHOLD: f32 = 0.06;
+// 섬유 수용량 범위 (종이 높이에 비례)
+const CAPACITY_MIN: f32 = 0.1;
+const CAPACITY_MAX: f32 = 0.35;
+// 이 포화도 이상일 때만 이웃으로 확산
+const DIFFUSE_MIN: f32 = 0.02;
+// 번짐(블룸): 새 물이 이 수위 이상이고, 이웃이 표면은 말랐지만 섬유가 촉촉할 때 발생
+const BLOOM_FRESH_WATER: f32 = 0.05;
+const BLOOM_DRY_SURFACE: f32 = 0.01;
+const BLOOM_DAMP_MIN: f32 = 0.03;
+const BLOOM_LIFT: f32 = 0.8;
+// 가장자리 어두워짐: 이 거리(셀) 안쪽에서 가장자리로 향하는 흐름과 추가 증발이 생긴다
+const EDGE_RANGE: f32 = 8.0;
+const EDGE_FLOW: f32 = 20.0;
+// 과립화: 골짜기로 모이는 속도, 밀도가 이 값인 안료를 기준(1배)으로 한다
+const GRANULATION_RATE: f32 = 0.8;
+const GRANULATION_DENSITY: f32 = 0.07;
+// 응집: 덩어리 노이즈의 크기(셀)와 덩어리 쪽으로 모이는 속도
+const FLOC_SCALE: f32 = 2.5;
+const FLOCCULATION_RATE: f32 = 0.8;
+// 착색력 0인 안료의 들어올리기 배율 (착색력 1/3 안료가 기존 6% 들어올림과 같다)
+const LIFT_GAIN: f32 = 1.5;
+// 펜 기울기가 없는 붓 자국 타원의 폭/길이 (apply_brush, 곡선 획, 물/흐리기/섞기 붓)
+const BRUSH_ASPECT: f32 = 0.7;
+// 머금은 붓이 담는 양: 붓 반지름² x 이 값 (붓 자국 하나가 내려놓는 양의 약 2/3 x 반지름²)
+const BRUSH_BELLY: f32 = 24.0;
+// 목마른 붓이 자국 한 번에 빨아들이는 표면 물 비율
+const BRUSH_PICKUP: f32 = 0.5;
+// 붓에 남은 물이 이보다 적으면 마른 붓: 종이 결의 봉우리에만 닿고 안료는 바로 침착된다
+const DRY_BRUSH: f32 = 0.3;
+// 마른 붓이 닿는 종이 높이 문턱의 바닥 (막 마르기 시작한 붓은 거의 모든 결에 닿는다)과 문턱 폭
+const DRY_PEAK_LOW: f32 = 0.2;
+const DRY_EDGE: f32 = 0.05;
+// 되돌리기 기록이 차지할 수 있는 바이트 (가장 최근 체크포인트 사본 하나는 따로)
+const HISTORY_BYTES: usize = 128 << 20;
+// replay_scaled 최대 배율
+const MAX_REPLAY_SCALE: u32 = 8;
+// 렌더 디버그 뷰 (set_debug_view)
+const DEBUG_VIEW_NONE: u32 = 0;
+const DEBUG_VIEW_GRANULATION: u32 = 1;
+const DEBUG_VIEW_FLOCCULATION: u32 = 2;
+// set_pigment_props 값은 안료 고유 성질에 곱하는 전역 배율로 쓰인다 (기본값 = 1배)
+const DEFAULT_ADHESION: f32 = 0.05;
+const DEFAULT_GRANULARITY: f32 = 0.8;
+
+// 붓 자국이 닿은 셀 하나. contact는 붓털이 닿는 정도 (가우시안 x 종이 결 x 붓털 노이즈),
+// allow는 실루엣이 허락하는 정도, edge는 자국 가장자리 안료 배율, spread는 젖은 종이에서 번지는 배율이다.
+struct DabCell {
+    idx: usize,
+    contact: f32,
+    allow: f32,
+    edge: f32,
+    spread: f32,
+}
+
+// open_stroke()가 연 것들 (close_stroke()에서 닫는다)
+#[derive(Clone, Copy)]
+struct CallScope {
+    checkpoint: bool,
+    journaled: bool,
 }
 
 fn rng(seed: &mut u32) -> f32 {
@@ -53,8 +198,263 @@ fn rng(seed: &mut u32) -> f32 {
     (*seed as f32) / (u32::MAX as f32)
 }
 
+// 셀에서 빠져나가는 순유량
+fn cell_divergence(u: &[f32], v: &[f32], w: usize, idx: usize) -> f32 {
+    let ku = (idx / w) * (w + 1) + idx % w;
+    u[ku + 1] - u[ku] + v[idx + w] - v[idx]
+}
+
+// 경계면 비율: (|속도| * dt).min(0.25) * (0.15 + 양쪽 칸 투과율 중 작은 값 * 0.85)
+fn face_fractions(vel: &[f32], ta: &[f32], tb: &[f32], dt: f32, out: &mut [f32]) {
+    let splat = F32x4::splat;
+    for k in (0..out.len()).step_by(LANES) {
+        let n = LANES.min(out.len() - k);
+        let load = |src: &[f32]| {
+            if n == LANES {
+                F32x4::load(&src[k..])
+            } else {
+                F32x4::load_partial(&src[k..k + n])
+            }
+        };
+        let t = load(ta).min(load(tb));
+        let frac = load(vel)
+            .abs()
+            .mul(splat(dt))
+            .min(splat(0.25))
+            .mul(splat(0.15).add(t.mul(splat(0.85))));
+        if n == LANES {
+            frac.store(&mut out[k..]);
+        } else {
+            frac.store_partial(&mut out[k..k + n]);
+        }
+    }
+}
+
+// move_fluid 한 번에 쓰는 경계면 속도와 옮길 비율
+struct Faces<'a> {
+    u: &'a [f32],
+    v: &'a [f32],
+    frac_u: &'a [f32],
+    frac_v: &'a [f32],
+    tiles: &'a Tiles,
+    w: usize,
+    h: usize,
+}
+
+impl Faces<'_> {
+    // 풍상 플럭스로 i번째 행에서 돌리는 칸의 새 값을 만든다 (나머지 칸은 그대로 둔다).
+    // 캔버스 가장자리와 구간 끝 칸은 하나씩, 안쪽은 4칸씩 묶어 같은 순서로 더한다.
+    fn gather_row(&self, src: &[f32], i: usize, row: &mut [f32]) {
+        let w = self.w;
+        for &(x0, x1) in self.tiles.spans(i) {
+            let mut j = x0;
+            while j < x1 {
+                if j > 0 && j + LANES < w && j + LANES <= x1 {
+                    self.gather4(src, i, j).store(&mut row[j..]);
+                    j += LANES;
+                } else {
+                    row[j] = self.gather_cell(src, i, j);
+                    j += 1;
+                }
+            }
+        }
+    }
+
+    // 왼쪽, 오른쪽, 위, 아래 경계면 순서
+    fn gather_cell(&self, src: &[f32], i: usize, j: usize) -> f32 {
+        let (w, h, uw) = (self.w, self.h, self.w + 1);
+        let c = i * w + j;
+        let mut x = src[c];
+        if j > 0 {
+            let k = i * uw + j;
+            let vel = self.u[k];
+            if vel > 0.0 {
+                x += src[c - 1] * self.frac_u[k];
+            } else if vel < 0.0 {
+                x -= src[c] * self.frac_u[k];
+            }
+        }
+        if j + 1 < w {
+            let k = i * uw + j + 1;
+            let vel = self.u[k];
+            if vel > 0.0 {
+                x -= src[c] * self.frac_u[k];
+            } else if vel < 0.0 {
+                x += src[c + 1] * self.frac_u[k];
+            }
+        }
+        if i > 0 {
+            let vel = self.v[c];
+            if vel > 0.0 {
+                x += src[c - w] * self.frac_v[c];
+            } else if vel < 0.0 {
+                x -= src[c] * self.frac_v[c];
+            }
+        }
+        if i + 1 < h {
+            let k = c + w;
+            let vel = self.v[k];
+            if vel > 0.0 {
+                x -= src[c] * self.frac_v[k];
+            } else if vel < 0.0 {
+                x += src[c + w] * self.frac_v[k];
+            }
+        }
+        x
+    }
+
+    // gather_cell의 4칸 묶음판 (0 < j, j + 4 < w)
+    fn gather4(&self, src: &[f32], i: usize, j: usize) -> F32x4 {
+        let (w, h, uw) = (self.w, self.h, self.w + 1);
+        let zero = F32x4::splat(0.0);
+        let c = i * w + j;
+        let here = F32x4::load(&src[c..]);
+        // 경계면 하나: 양의 속도는 왼쪽/위에서 오른쪽/아래로 흐른다.
+        // incoming이면 이 칸이 경계면의 오른쪽/아래 칸이고 from은 반대편 칸이다.
+        let face = |x: F32x4, vel: F32x4, frac: F32x4, from: F32x4, incoming: bool| {
+            let (pos, neg) = (zero.lt(vel), vel.lt(zero));
+            if incoming {
+                pos.select(x.add(from.mul(frac)), neg.select(x.sub(here.mul(frac)), x))
+            } else {
+                pos.select(x.sub(here.mul(frac)), neg.select(x.add(from.mul(frac)), x))
+            }
+        };
+        let k = i * uw + j;
+        let frac_u = |k: usize| F32x4::load(&self.frac_u[k..]);
+        let mut x = face(
+            here,
+            F32x4::load(&self.u[k..]),
+            frac_u(k),
+            F32x4::load(&src[c - 1..]),
+            true,
+        );
+        x = face(
+            x,
+            F32x4::load(&self.u[k + 1..]),
+            frac_u(k + 1),
+            F32x4::load(&src[c + 1..]),
+            false,
+        );
+        if i > 0 {
+            x = face(
+                x,
+                F32x4::load(&self.v[c..]),
+                F32x4::load(&self.frac_v[c..]),
+                F32x4::load(&src[c - w..]),
+                true,
+            );
+        }
+        if i + 1 < h {
+            x = face(
+                x,
+                F32x4::load(&self.v[c + w..]),
+                F32x4::load(&self.frac_v[c + w..]),
+                F32x4::load(&src[c + w..]),
+                false,
+            );
+        }
+        x
+    }
+}
+
+// 셀 좌표 해시 노이즈 (0..1)
+fn cell_noise(x: u32, y: u32) -> f32 {
+    let hash = x.wrapping_mul(73856093) ^ y.wrapping_mul(19349663);
+    (hash as f32) / (u32::MAX as f32)
+}
+
+// 격자점 해시 (이웃 격자점끼리 상관이 없도록 비트를 충분히 섞는다)
+fn lattice_noise(x: u32, y: u32, seed: u32) -> f32 {
+    let mut hash =
+        x.wrapping_mul(0x9e37_79b9) ^ y.wrapping_mul(0x85eb_ca6b) ^ seed.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb_352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846c_a68b);
+    hash ^= hash >> 16;
+    (hash as f32) / (u32::MAX as f32)
+}
+
+// 안료마다 다른 덩어리 모양을 주는 값 노이즈 (0..1, 종이 높이와 무관), size는 덩어리 크기(셀)
+fn floc_noise(x: usize, y: usize, seed: u32, size: f32) -> f32 {
+    let fx = x as f32 / size;
+    let fy = y as f32 / size;
+    let x0 = fx.floor();
+    let y0 = fy.floor();
+    let tx = fx - x0;
+    let ty = fy - y0;
+    let (ix, iy) = (x0 as u32, y0 as u32);
+    let a = lattice_noise(ix, iy, seed);
+    let b = lattice_noise(ix + 1, iy, seed);
+    let c = lattice_noise(ix, iy + 1, seed);
+    let d = lattice_noise(ix + 1, iy + 1, seed);
+    let top = a + (b - a) * tx;
+    let bottom = c + (d - c) * tx;
+    top + (bottom - top) * ty
+}
+
+// 한 행의 floc_noise: 격자점 해시를 행마다 한 번만 구해 두고 보간만 한다 (값은 floc_noise와 같다)
+struct FlocRow {
+    top: Vec<f32>,
+    bottom: Vec<f32>,
+    ty: f32,
+    size: f32,
+}
+
+impl FlocRow {
+    fn new(y: usize, width: usize, seed: u32, size: f32) -> Self {
+        let fy = y as f32 / size;
+        let y0 = fy.floor();
+        let iy = y0 as u32;
+        let cols = ((width - 1) as f32 / size).floor() as usize + 2;
+        FlocRow {
+            top: (0..cols).map(|ix| lattice_noise(ix as u32, iy, seed)).collect(),
+            bottom: (0..cols).map(|ix| lattice_noise(ix as u32, iy + 1, seed)).collect(),
+            ty: fy - y0,
+            size,
+        }
+    }
+
+    // x부터 네 칸 (행 끝을 넘는 칸은 마지막 칸 값으로 채운다)
+    fn at4(&self, x: usize, width: usize) -> F32x4 {
+        let cols: [f32; LANES] = std::array::from_fn(|k| (x + k).min(width - 1) as f32);
+        let fx = F32x4::from_array(cols).div(F32x4::splat(self.size)).to_array();
+        // 네 모서리 (왼쪽 위, 오른쪽 위, 왼쪽 아래, 오른쪽 아래)
+        let mut corner = [[0.0; LANES]; 4];
+        let mut tx = [0.0; LANES];
+        for k in 0..LANES {
+            // fx >= 0이라 정수 변환이 floor와 같다 (SSE2에는 floor 명령이 없다)
+            let ix = fx[k] as usize;
+            tx[k] = fx[k] - ix as f32;
+            corner[0][k] = self.top[ix];
+            corner[1][k] = self.top[ix + 1];
+            corner[2][k] = self.bottom[ix];
+            corner[3][k] = self.bottom[ix + 1];
+        }
+        let lerp = |p: [f32; LANES], q: [f32; LANES], t: F32x4| {
+            let p = F32x4::from_array(p);
+            p.add(F32x4::from_array(q).sub(p).mul(t))
+        };
+        let tx = F32x4::from_array(tx);
+        let top = lerp(corner[0], corner[1], tx);
+        let bottom = lerp(corner[2], corner[3], tx);
+        top.add(bottom.sub(top).mul(F32x4::splat(self.ty)))
+    }
+}
+
+// 마른 붓이면 (닿는 종이 높이 문턱, 마른 정도 0..1). 물이 적고 누르는 힘이 약할수록 문턱이 높다.
+fn dry_peak(water: f32, pressure: f32) -> Option<(f32, f32)> {
+    if water >= DRY_BRUSH {
+        return None;
+    }
+    let dryness = (1.0 - water.max(0.0) / DRY_BRUSH).min(1.0);
+    let press = pressure.clamp(0.0, 1.0);
+    let threshold = DRY_PEAK_LOW + (1.0 - DRY_PEAK_LOW) * dryness * (1.0 - 0.5 * press);
+    Some((threshold, dryness))
+}
+
 fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
-    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
     t * t * (3.0 - 2.0 * t)
 }
 
@@ -75,26 +475,32 @@ impl WatercolorEngine {
                 let n2 = (i as f32 * 0.05).sin() * (j as f32 * 0.07).cos() * 0.08;
                 let n3 = (i as f32 * 0.15).cos() * (j as f32 * 0.11).sin() * 0.04;
                 let n4 = ((i as f32 * 0.3).sin() + (j as f32 * 0.25).cos()) * 0.02;
-                paper_h[idx] = (0.45 + n1 + n2 + n3 + n4).max(0.0).min(1.0);
+                paper_h[idx] = (0.45 + n1 + n2 + n3 + n4).clamp(0.0, 1.0);
             }
         }
         let paper_render = paper_h.clone();
 
-        WatercolorEngine {
+        let mut engine = WatercolorEngine {
             width,
             height,
             total,
             h: vec![0.0; total],
-            u: vec![0.0; total],
-            v: vec![0.0; total],
+            u: vec![0.0; (width + 1) * height],
+            v: vec![0.0; width * (height + 1)],
             p: vec![0.0; total],
             mask: vec![0.0; total],
-            gr: vec![0.0; total],
-            gg: vec![0.0; total],
-            gb: vec![0.0; total],
-            dr: vec![0.0; total],
-            dg: vec![0.0; total],
-            db: vec![0.0; total],
+            s: vec![0.0; total],
+            capacity: vec![0.0; total],
+            wet_mask: vec![0.0; total],
+            edge_dist: vec![0.0; total],
+            tiles: Tiles::new(width, height),
+            scratch: Vec::new(),
+            g: Vec::new(),
+            d: Vec::new(),
+            pigments: PigmentLibrary::builtin(),
+            palette: Vec::new(),
+            free_slots: None,
+            brush: Brush::default(),
             paper_h,
             paper_render,
             silhouette_map: vec![1.0; total],
@@ -105,12 +511,26 @@ impl WatercolorEngine {
             viscosity: 0.05,
             pressure: 5.0,
             iterations: 10,
-            adhesion: 0.05,
-            granularity: 0.8,
+            adhesion: DEFAULT_ADHESION,
+            granularity: DEFAULT_GRANULARITY,
+            absorption: 0.08,
+            capillary_diffusion: 1.0,
+            bloom_strength: 1.0,
+            edge_darkening: 1.0,
+            scale: 1.0,
+            divergence_residual: 0.0,
+            history: History::new(width, height, HISTORY_BYTES),
+            stroke_open: false,
+            journal: Journal::default(),
             show_texture: true,
+            debug_view: DEBUG_VIEW_NONE,
             silhouette_strength: 0.85,
             edge_bleed_strength: 0.35,
-        }
+            drybrush: false,
+            tip: None,
+        };
+        engine.rebuild_capacity_map();
+        engine
     }
 
     pub fn get_width(&self) -> u32 {
@@ -121,6 +541,11 @@ impl WatercolorEngine {
     }
 
     pub fn load_paper_texture(&mut self, data: &[u8], tex_w: u32, tex_h: u32) {
+        self.journal.record(|| Entry::Paper {
+            data: data.to_vec(),
+            width: tex_w,
+            height: tex_h,
+        });
         let tw = tex_w as usize;
         let th = tex_h as usize;
         for i in 0..self.height {
@@ -135,12 +560,15 @@ impl WatercolorEngine {
                         / 255.0;
                     let alpha = data[src_idx + 3] as f32 / 255.0;
                     self.paper_h[i * self.width + j] = gray;
-                    self.silhouette_map[i * self.width + j] = ((1.0 - gray) * alpha).max(0.0).min(1.0);
+                    self.silhouette_map[i * self.width + j] =
+                        ((1.0 - gray) * alpha).clamp(0.0, 1.0);
                 }
             }
         }
         self.rebuild_paper_render_map();
         self.rebuild_silhouette_edge_map();
+        self.rebuild_capacity_map();
+        self.tiles.touch_all();
     }
 
     pub fn set_physics(
@@ -151,6 +579,12 @@ impl WatercolorEngine {
         pressure: f32,
         iterations: u32,
     ) {
+        self.journal.record(|| {
+            Entry::Call(
+                Tool::SetPhysics,
+                vec![dt, evaporation, viscosity, pressure, iterations as f32],
+            )
+        });
         self.dt = dt;
         self.evaporation = evaporation;
         self.viscosity = viscosity;
@@ -158,20 +592,183 @@ impl WatercolorEngine {
         self.iterations = iterations;
     }
 
+    pub fn set_capillary_props(&mut self, absorption: f32, diffusion: f32) {
+        self.journal.record(|| Entry::Call(Tool::SetCapillaryProps, vec![absorption, diffusion]));
+        self.absorption = absorption.max(0.0);
+        self.capillary_diffusion = diffusion.clamp(0.0, 4.0);
+    }
+
+    pub fn set_bloom_strength(&mut self, strength: f32) {
+        self.journal.record(|| Entry::Call(Tool::SetBloomStrength, vec![strength]));
+        self.bloom_strength = strength.clamp(0.0, 4.0);
+    }
+
+    pub fn set_edge_darkening(&mut self, strength: f32) {
+        self.journal.record(|| Entry::Call(Tool::SetEdgeDarkening, vec![strength]));
+        self.edge_darkening = strength.clamp(0.0, 4.0);
+    }
+
+    // 깨어 있는 타일 비율 (0..1). step 비용은 대략 이 비율을 따른다.
+    pub fn active_area(&self) -> f32 {
+        self.tiles.awake_count() as f32 / self.tiles.count() as f32
+    }
+
+    pub fn divergence_residual(&self) -> f32 {
+        self.divergence_residual
+    }
+
     pub fn set_pigment_props(&mut self, adhesion: f32, granularity: f32) {
+        self.journal.record(|| Entry::Call(Tool::SetPigmentProps, vec![adhesion, granularity]));
         self.adhesion = adhesion;
         self.granularity = granularity;
+        // 종이 결 세기와 과립화 디버그 뷰가 granularity를 따른다
+        self.tiles.touch_all();
     }
 
     pub fn set_show_texture(&mut self, show: bool) {
+        self.journal.record(|| Entry::Call(Tool::SetShowTexture, vec![show as u32 as f32]));
         self.show_texture = show;
+        self.tiles.touch_all();
+    }
+
+    // 켜면 물이 적은 붓 자국(water < 0.3)은 종이 결의 봉우리에만 닿고, 마른 만큼 안료를 종이에 바로 침착한다.
+    // 물이 적을수록, 누르는 힘이 약할수록 더 높은 봉우리만 남아 끊기고 반짝이는 자국이 된다.
+    pub fn set_drybrush(&mut self, on: bool) {
+        self.journal.record(|| Entry::Call(Tool::SetDrybrush, vec![on as u32 as f32]));
+        self.drybrush = on;
+    }
+
+    // 0: 둥근 붓 (기본), 1: 납작붓, 2: 필버트, 3: 부채붓, 4: 리거.
+    // 붓끝 모양은 붓 자국을 쓰는 붓(apply_brush 계열, 펜, 곡선 획, 머금은 붓)에 적용된다.
+    pub fn set_brush_tip(&mut self, preset: u32) {
+        self.journal.record(|| Entry::Call(Tool::SetBrushTip, vec![preset as f32]));
+        self.tip = Tip::preset(preset);
+    }
+
+    // 회색조 붓끝 이미지 (RGBA, load_paper_texture와 같은 형식). 어두울수록 붓털이 많이 닿는다.
+    // 가로가 획 진행 방향이다. 크기가 맞지 않으면 무시한다.
+    pub fn load_brush_tip(&mut self, data: &[u8], tip_w: u32, tip_h: u32) {
+        let Some(tip) = Tip::from_rgba(data, tip_w, tip_h) else {
+            return;
+        };
+        self.journal.record(|| Entry::Tip {
+            data: data.to_vec(),
+            width: tip_w,
+            height: tip_h,
+        });
+        self.tip = Some(tip);
+    }
+
+    // 0: 둥근 붓, 1..4: 프리셋, 5: 불러온 이미지
+    pub fn brush_tip(&self) -> u32 {
+        self.tip.as_ref().map_or(tip::ROUND, |t| t.kind)
+    }
+
+    // 0: 일반, 1: 과립화 기여, 2: 응집 기여
+    pub fn set_debug_view(&mut self, mode: u32) {
+        self.journal.record(|| Entry::Call(Tool::SetDebugView, vec![mode as f32]));
+        self.debug_view = mode.min(DEBUG_VIEW_FLOCCULATION);
+        self.tiles.touch_all();
     }
 
     pub fn set_silhouette_controls(&mut self, silhouette_strength: f32, edge_bleed_strength: f32) {
-        self.silhouette_strength = silhouette_strength.max(0.0).min(1.5);
-        self.edge_bleed_strength = edge_bleed_strength.max(0.0).min(2.0);
+        self.journal.record(|| {
+            Entry::Call(
+                Tool::SetSilhouetteControls,
+                vec![silhouette_strength, edge_bleed_strength],
+            )
+        });
+        self.silhouette_strength = silhouette_strength.clamp(0.0, 1.5);
+        self.edge_bleed_strength = edge_bleed_strength.clamp(0.0, 2.0);
+    }
+
+    pub fn pigment_count(&self) -> u32 {
+        self.pigments.len() as u32
+    }
+
+    pub fn pigment_name(&self, id: u32) -> String {
+        self.pigments
+            .get(id)
+            .map(|p| p.name.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn find_pigment(&self, name: &str) -> i32 {
+        self.pigments.find(name).map(|id| id as i32).unwrap_or(-1)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_pigment(
+        &mut self,
+        name: &str,
+        k_r: f32,
+        k_g: f32,
+        k_b: f32,
+        s_r: f32,
+        s_g: f32,
+        s_b: f32,
+        density: f32,
+        staining: f32,
+        granulation: f32,
+        flocculation: f32,
+    ) -> u32 {
+        self.add_pigment(Pigment::new(
+            name,
+            [k_r, k_g, k_b],
+            [s_r, s_g, s_b],
+            density,
+            staining,
+            granulation,
+            flocculation,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_pigment_rgb(
+        &mut self,
+        name: &str,
+        r: f32,
+        g: f32,
+        b: f32,
+        density: f32,
+        staining: f32,
+        granulation: f32,
+        flocculation: f32,
+    ) -> u32 {
+        self.add_pigment(Pigment::from_rgb(
+            name,
+            r,
+            g,
+            b,
+            density,
+            staining,
+            granulation,
+            flocculation,
+        ))
+    }
+
+    pub fn palette_len(&self) -> u32 {
+        self.palette.len() as u32
+    }
+
+    // 팔레트에 없는 안료로는 더 칠할 수 없는지 (슬롯이 모두 차 있고 비울 수 있는 슬롯도 없다).
+    // 이때 새 안료로 칠한 붓 자국에는 물만 남는다.
+    pub fn palette_full(&self) -> bool {
+        self.palette.len() >= MAX_PIGMENTS
+            && self
+                .free_slots
+                .as_ref()
+                .map_or_else(|| self.spent_slots().is_empty(), |f| f.is_empty())
+    }
+
+    pub fn palette_pigment(&self, slot: u32) -> i32 {
+        self.palette
+            .get(slot as usize)
+            .map(|&id| id as i32)
+            .unwrap_or(-1)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_brush(
         &mut self,
         cx: i32,
@@ -179,50 +776,127 @@ impl WatercolorEngine {
         size: f32,
         water: f32,
         pigment_amount: f32,
-        r: f32,
-        g: f32,
-        b: f32,
+        pigment: u32,
+        angle: f32,
+        pressure: f32,
+    ) {
+        let scope = self.open_stroke(
+            Tool::Brush,
+            &[cx as f32, cy as f32, size, water, pigment_amount, pigment as f32, angle, pressure],
+        );
+        let slot = self.pigment_slot(pigment);
+        let (x, y) = (cx as f32, cy as f32);
+        self.dab(x, y, size, water, pigment_amount, slot, angle, pressure, BRUSH_ASPECT);
+        self.close_stroke(scope);
+    }
+
+    // 타원 붓 자국 하나. size는 angle 방향 반지름, aspect는 그 수직 방향 비율이다.
+    // 중심은 셀 안 어디든 될 수 있다 (셀 중심 = 정수 좌표).
+    #[allow(clippy::too_many_arguments)]
+    fn dab(
+        &mut self,
+        cx: f32,
+        cy: f32,
+        size: f32,
+        water: f32,
+        pigment_amount: f32,
+        slot: Option<usize>,
         angle: f32,
         pressure: f32,
+        aspect: f32,
+    ) {
+        if let Some((threshold, dryness)) = dry_peak(water, pressure).filter(|_| self.drybrush) {
+            self.dab_cells(cx, cy, size, angle, aspect, |e, c| {
+                let peak = smoothstep(threshold - DRY_EDGE, threshold + DRY_EDGE, e.paper_h[c.idx]);
+                let brush_factor = c.contact * pressure * c.allow * peak;
+
+                e.h[c.idx] += water * brush_factor * c.spread * 0.7;
+                let pig_factor = pigment_amount * brush_factor * c.edge * 0.5;
+                if let Some(slot) = slot {
+                    e.d[slot][c.idx] += pig_factor * dryness;
+                    e.g[slot][c.idx] += pig_factor * (1.0 - dryness);
+                }
+                e.mask[c.idx] = 1.0;
+            });
+            return;
+        }
+        self.dab_cells(cx, cy, size, angle, aspect, |e, c| {
+            let brush_factor = c.contact * pressure * c.allow;
+
+            e.h[c.idx] += water * brush_factor * c.spread * 0.7;
+            let pig_factor = pigment_amount * brush_factor * c.edge * 0.5;
+            if let Some(slot) = slot {
+                e.g[slot][c.idx] += pig_factor;
+            }
+            e.mask[c.idx] = 1.0;
+        });
+    }
+
+    // 타원 붓 자국이 닿는 셀마다 f를 부른다 (실루엣이 막는 셀은 건너뛴다)
+    fn dab_cells(
+        &mut self,
+        cx: f32,
+        cy: f32,
+        size: f32,
+        angle: f32,
+        aspect: f32,
+        mut f: impl FnMut(&mut Self, DabCell),
     ) {
         let w = self.width as i32;
         let h = self.height as i32;
         let radius = size.max(0.5);
-        let isize = radius.ceil() as i32;
+        let (ix, iy) = (cx.round() as i32, cy.round() as i32);
+        let off = (cx - ix as f32).abs().max((cy - iy as f32).abs());
+        // 붓끝 이미지는 aspect 대신 제 모양을 쓰고, 돌아간 네모의 모서리까지 훑는다
+        let tip = self.tip.take();
+        let reach = if tip.is_some() { radius * std::f32::consts::SQRT_2 } else { radius };
+        let isize = (reach + off).ceil() as i32;
+        self.wake_around(ix, iy, isize);
         let sigma = radius * 0.45;
         let sigma2 = sigma * sigma;
         let cos_a = angle.cos();
         let sin_a = angle.sin();
-        let aspect = 0.7;
 
         for di in -isize..=isize {
             for dj in -isize..=isize {
-                let tx = cx + di;
-                let ty = cy + dj;
+                let tx = ix + di;
+                let ty = iy + dj;
                 if tx < 0 || tx >= w || ty < 0 || ty >= h {
                     continue;
                 }
 
                 let idx = ty as usize * self.width + tx as usize;
-                let fi = di as f32;
-                let fj = dj as f32;
+                let fi = tx as f32 - cx;
+                let fj = ty as f32 - cy;
                 let rot_x = fi * cos_a + fj * sin_a;
-                let rot_y = (-fi * sin_a + fj * cos_a) / aspect;
-                let dist_sq = rot_x * rot_x + rot_y * rot_y;
-                let dist = dist_sq.sqrt();
-                if dist > radius {
-                    continue;
-                }
-
-                let gaussian = (-dist_sq / (2.0 * sigma2)).exp();
-                let bristle_noise = {
-                    let hash =
-                        ((tx as u32).wrapping_mul(73856093)) ^ ((ty as u32).wrapping_mul(19349663));
-                    0.6 + (hash as f32) / (u32::MAX as f32) * 0.4
+                let across = -fi * sin_a + fj * cos_a;
+                let (gaussian, bristle_noise, norm_dist) = match &tip {
+                    Some(tip) => {
+                        let cover = tip.sample(rot_x / radius, across / radius);
+                        if cover <= 0.0 {
+                            continue;
+                        }
+                        // 붓털 결은 이미지에 들어 있고, 성글게 닿는 곳을 붓 자국 가장자리로 본다
+                        (cover, 1.0, 1.0 - cover)
+                    }
+                    None => {
+                        let rot_y = across / aspect;
+                        let dist_sq = rot_x * rot_x + rot_y * rot_y;
+                        let dist = dist_sq.sqrt();
+                        if dist > radius {
+                            continue;
+                        }
+                        let hash = ((tx as u32).wrapping_mul(73856093))
+                            ^ ((ty as u32).wrapping_mul(19349663));
+                        (
+                            (-dist_sq / (2.0 * sigma2)).exp(),
+                            0.6 + (hash as f32) / (u32::MAX as f32) * 0.4,
+                            dist / radius,
+                        )
+                    }
                 };
                 let paper_val = self.paper_h[idx];
                 let paper_response = 0.6 + 0.4 * (1.0 - paper_val);
-                let norm_dist = dist / radius;
                 let edge_factor = 1.0 + smoothstep(0.5, 0.95, norm_dist) * 0.6;
                 let wetness = self.h[idx].min(1.0);
                 let wet_spread = 1.0 + wetness * 0.4;
@@ -233,22 +907,24 @@ impl WatercolorEngine {
                     * self.silhouette_edge[idx]
                     * smoothstep(0.65, 1.0, norm_dist)
                     * (1.0 - silhouette);
-                let allow = (base_allow + edge_escape).max(0.0).min(1.0);
+                let allow = (base_allow + edge_escape).clamp(0.0, 1.0);
                 if allow <= 0.001 {
                     continue;
                 }
-                let brush_factor = gaussian * paper_response * bristle_noise * pressure * allow;
-
-                self.h[idx] += water * brush_factor * wet_spread * 0.7;
-                let pig_factor = pigment_amount * brush_factor * edge_factor * 0.5;
-                self.gr[idx] += (1.0 - r) * pig_factor;
-                self.gg[idx] += (1.0 - g) * pig_factor;
-                self.gb[idx] += (1.0 - b) * pig_factor;
-                self.mask[idx] = 1.0;
+                let cell = DabCell {
+                    idx,
+                    contact: gaussian * paper_response * bristle_noise,
+                    allow,
+                    edge: edge_factor,
+                    spread: wet_spread,
+                };
+                f(self, cell);
             }
         }
+        self.tip = tip;
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_brush_stroke(
         &mut self,
         x0: i32,
@@ -258,16 +934,28 @@ impl WatercolorEngine {
         size: f32,
         water: f32,
         pigment_amount: f32,
-        r: f32,
-        g: f32,
-        b: f32,
+        pigment: u32,
         velocity: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::BrushStroke,
+            &[
+                x0 as f32,
+                y0 as f32,
+                x1 as f32,
+                y1 as f32,
+                size,
+                water,
+                pigment_amount,
+                pigment as f32,
+                velocity,
+            ],
+        );
         let dx = (x1 - x0) as f32;
         let dy = (y1 - y0) as f32;
         let length = (dx * dx + dy * dy).sqrt();
         let angle = dy.atan2(dx);
-        let pressure = (1.0 / (1.0 + velocity * 0.08)).max(0.2).min(1.0);
+        let pressure = (1.0 / (1.0 + velocity * 0.08)).clamp(0.2, 1.0);
         let step_size = (size * 0.3).max(0.5);
         let steps = (length / step_size).ceil().max(1.0) as i32;
 
@@ -284,13 +972,318 @@ impl WatercolorEngine {
                 size,
                 water * attenuation,
                 pigment_amount * attenuation,
-                r,
-                g,
-                b,
+                pigment,
                 angle,
                 pressure * attenuation,
             );
         }
+        self.close_stroke(scope);
+    }
+
+    // 펜 입력 붓 자국 하나. pressure는 0..1, tilt_x/tilt_y/twist는 PointerEvent의 도 단위 값이다.
+    // 세운 펜은 둥근 자국을 남기고, 눕힐수록 기운 쪽으로 길고 좁아진다. twist는 자국을 돌린다.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_pen_brush(
+        &mut self,
+        cx: i32,
+        cy: i32,
+        size: f32,
+        water: f32,
+        pigment_amount: f32,
+        pigment: u32,
+        pressure: f32,
+        tilt_x: f32,
+        tilt_y: f32,
+        twist: f32,
+    ) {
+        let scope = self.open_stroke(
+            Tool::PenBrush,
+            &[
+                cx as f32,
+                cy as f32,
+                size,
+                water,
+                pigment_amount,
+                pigment as f32,
+                pressure,
+                tilt_x,
+                tilt_y,
+                twist,
+            ],
+        );
+        let pen = PenSample {
+            pressure,
+            tilt_x,
+            tilt_y,
+            twist,
+        };
+        self.pen_dab(cx as f32, cy as f32, size, water, pigment_amount, pigment, &pen, 0.0);
+        self.close_stroke(scope);
+    }
+
+    // 펜 표본 두 개 사이를 잇는 획. 압력, 기울기, 회전은 두 표본 사이에서 보간한다.
+    // 펜을 세운 구간에서는 붓 자국의 긴 축이 획 방향을 따른다.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_pen_stroke(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        size: f32,
+        water: f32,
+        pigment_amount: f32,
+        pigment: u32,
+        pressure0: f32,
+        tilt_x0: f32,
+        tilt_y0: f32,
+        twist0: f32,
+        pressure1: f32,
+        tilt_x1: f32,
+        tilt_y1: f32,
+        twist1: f32,
+    ) {
+        let scope = self.open_stroke(
+            Tool::PenStroke,
+            &[
+                x0 as f32,
+                y0 as f32,
+                x1 as f32,
+                y1 as f32,
+                size,
+                water,
+                pigment_amount,
+                pigment as f32,
+                pressure0,
+                tilt_x0,
+                tilt_y0,
+                twist0,
+                pressure1,
+                tilt_x1,
+                tilt_y1,
+                twist1,
+            ],
+        );
+        let start = PenSample {
+            pressure: pressure0,
+            tilt_x: tilt_x0,
+            tilt_y: tilt_y0,
+            twist: twist0,
+        };
+        let end = PenSample {
+            pressure: pressure1,
+            tilt_x: tilt_x1,
+            tilt_y: tilt_y1,
+            twist: twist1,
+        };
+        let dx = (x1 - x0) as f32;
+        let dy = (y1 - y0) as f32;
+        let length = (dx * dx + dy * dy).sqrt();
+        let heading = dy.atan2(dx);
+        let step_size = (size * 0.3).max(0.5);
+        let steps = (length / step_size).ceil().max(1.0) as i32;
+
+        for s in 0..=steps {
+            let t = s as f32 / steps as f32;
+            let x = x0 as f32 + dx * t;
+            let y = y0 as f32 + dy * t;
+            let pen = start.lerp(&end, t);
+            self.pen_dab(
+                x,
+                y,
+                size,
+                water,
+                pigment_amount,
+                pigment,
+                &pen,
+                heading,
+            );
+        }
+        self.close_stroke(scope);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn pen_dab(
+        &mut self,
+        cx: f32,
+        cy: f32,
+        size: f32,
+        water: f32,
+        pigment_amount: f32,
+        pigment: u32,
+        pen: &PenSample,
+        heading: f32,
+    ) {
+        let slot = self.pigment_slot(pigment);
+        let fp = pen.footprint(size, heading);
+        self.dab(cx, cy, fp.radius, water, pigment_amount, slot, fp.angle, fp.load, fp.aspect);
+    }
+
+    // 표본 열을 부드러운 곡선으로 잇는 획. samples는 (x, y, 압력, 시각 ms) 네 값씩 이어 붙인 배열이다.
+    // 좌표는 실수 그대로 붓 자국 중심이 되고, 압력은 붓 크기와 물/안료 양을,
+    // 표본 사이 이동 속도는 apply_brush_stroke의 velocity처럼 물감 양을 정한다.
+    pub fn apply_spline_stroke(
+        &mut self,
+        samples: &[f32],
+        size: f32,
+        water: f32,
+        pigment_amount: f32,
+        pigment: u32,
+    ) {
+        let mut args = vec![size, water, pigment_amount, pigment as f32];
+        args.extend_from_slice(&samples[..samples.len() / SAMPLE * SAMPLE]);
+        let scope = self.open_stroke(Tool::SplineStroke, &args);
+        let slot = self.pigment_slot(pigment);
+        let step_size = (size * 0.3).max(0.5);
+        for d in spline::dabs(samples, step_size) {
+            let speed = (1.0 / (1.0 + d.velocity * 0.08)).clamp(0.2, 1.0);
+            let load = d.pressure.clamp(0.0, 1.0) * speed;
+            let radius = pen::pressure_size(size, d.pressure);
+            self.dab(d.x, d.y, radius, water, pigment_amount, slot, d.angle, load, BRUSH_ASPECT);
+        }
+        self.close_stroke(scope);
+    }
+
+    // 붓에 물감을 묻힌다. 물은 water, pigment 안료는 pigment_amount만큼 머금고 묻어 있던 다른 안료는 조금 남는다.
+    pub fn load_brush(&mut self, water: f32, pigment_amount: f32, pigment: u32) {
+        self.journal.record(|| {
+            Entry::Call(Tool::LoadBrush, vec![water, pigment_amount, pigment as f32])
+        });
+        self.brush.dip(water, pigment, pigment_amount);
+    }
+
+    // 붓을 헹군다 (안료만 빠지고 물은 남는다)
+    pub fn rinse_brush(&mut self) {
+        self.journal.record(|| Entry::Call(Tool::RinseBrush, Vec::new()));
+        self.brush.rinse();
+    }
+
+    pub fn brush_water(&self) -> f32 {
+        self.brush.water
+    }
+
+    pub fn brush_pigment(&self) -> f32 {
+        self.brush.total_pigment()
+    }
+
+    // 머금은 붓으로 자국 하나
+    pub fn apply_loaded_brush(&mut self, cx: i32, cy: i32, size: f32, angle: f32, pressure: f32) {
+        let scope = self.open_stroke(
+            Tool::LoadedBrush,
+            &[cx as f32, cy as f32, size, angle, pressure],
+        );
+        self.loaded_dab(cx as f32, cy as f32, size, angle, pressure);
+        self.close_stroke(scope);
+    }
+
+    // 머금은 붓으로 긋는 획. 물감 양은 붓에 남은 만큼이라 획을 나누어 그어도 이어서 줄어든다.
+    pub fn apply_loaded_brush_stroke(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        size: f32,
+        velocity: f32,
+    ) {
+        let scope = self.open_stroke(
+            Tool::LoadedBrushStroke,
+            &[x0 as f32, y0 as f32, x1 as f32, y1 as f32, size, velocity],
+        );
+        let dx = (x1 - x0) as f32;
+        let dy = (y1 - y0) as f32;
+        let length = (dx * dx + dy * dy).sqrt();
+        let angle = dy.atan2(dx);
+        let pressure = (1.0 / (1.0 + velocity * 0.08)).clamp(0.2, 1.0);
+        let step_size = (size * 0.3).max(0.5);
+        let steps = (length / step_size).ceil().max(1.0) as i32;
+
+        for s in 0..=steps {
+            let t = s as f32 / steps as f32;
+            let x = x0 as f32 + dx * t;
+            let y = y0 as f32 + dy * t;
+            self.loaded_dab(x, y, size, angle, pressure);
+        }
+        self.close_stroke(scope);
+    }
+
+    // 붓에 든 물과 안료를 내려놓고, 자국 아래 젖은 물감(표면 물과 부유 안료)을 빨아들인다.
+    // 주고받은 양은 붓이 담는 양(BRUSH_BELLY x 반지름²)에 대한 비율로 붓에 반영한다.
+    fn loaded_dab(&mut self, cx: f32, cy: f32, size: f32, angle: f32, pressure: f32) {
+        let held: Vec<(u32, Option<usize>, f32)> = self
+            .brush
+            .pigment
+            .clone()
+            .into_iter()
+            .map(|(id, amount)| (id, self.pigment_slot(id), amount))
+            .collect();
+        let slots = self.palette.len();
+        let mut load = vec![0.0; slots];
+        for &(_, slot, amount) in &held {
+            if let Some(slot) = slot {
+                load[slot] += amount;
+            }
+        }
+        let water = self.brush.water;
+        let thirst = (1.0 - water).max(0.0);
+        // 말라 가는 붓은 늘 마른 붓처럼 칠한다
+        let dry = dry_peak(water, pressure);
+        let mut given = 0.0;
+        let mut taken = 0.0;
+        let mut put = vec![0.0; slots];
+        let mut lifted = vec![0.0; slots];
+
+        self.dab_cells(cx, cy, size, angle, BRUSH_ASPECT, |e, c| {
+            let idx = c.idx;
+            let touch = c.contact * pressure * c.allow;
+            let (bite, dryness) = match dry {
+                Some((threshold, dryness)) => {
+                    let paper = e.paper_h[idx];
+                    let peak = smoothstep(threshold - DRY_EDGE, threshold + DRY_EDGE, paper);
+                    (touch * peak, dryness)
+                }
+                None => (touch, 0.0),
+            };
+            let h0 = e.h[idx];
+            let sip = (h0 * BRUSH_PICKUP * thirst * touch).max(0.0).min(h0.max(0.0));
+            let frac = if h0 > 0.0 { sip / h0 } else { 0.0 };
+            let out = water * bite * c.spread * 0.7;
+            e.h[idx] = h0 - sip + out;
+            given += out;
+            taken += sip;
+            for slot in 0..slots {
+                let lift = e.g[slot][idx] * frac;
+                let drop = load[slot] * bite * c.edge * 0.5;
+                e.g[slot][idx] += drop * (1.0 - dryness) - lift;
+                e.d[slot][idx] += drop * dryness;
+                put[slot] += drop;
+                lifted[slot] += lift;
+            }
+            e.mask[idx] = 1.0;
+        });
+
+        let belly = BRUSH_BELLY * size.max(0.5) * size.max(0.5);
+        self.brush.water = (water + (taken - given) / belly).max(0.0);
+        // 내려놓은 안료는 같은 슬롯의 안료에서 비율대로 빠지고, 빨아들인 안료는 슬롯의 안료로 묻는다
+        self.brush.pigment = held
+            .iter()
+            .map(|&(id, slot, amount)| {
+                let spent = slot.map_or(0.0, |s| {
+                    if load[s] > 0.0 {
+                        (put[s] / belly / load[s]).min(1.0)
+                    } else {
+                        0.0
+                    }
+                });
+                (id, amount * (1.0 - spent))
+            })
+            .collect();
+        for (slot, &lift) in lifted.iter().enumerate() {
+            if lift > 0.0 {
+                self.brush.add(self.palette[slot], lift / belly);
+            }
+        }
+        self.brush.shake();
     }
 
     pub fn apply_background_brush(
@@ -300,14 +1293,18 @@ impl WatercolorEngine {
         size: f32,
         water: f32,
         pigment_amount: f32,
-        r: f32,
-        g: f32,
-        b: f32,
+        pigment: u32,
     ) {
+        let scope = self.open_stroke(
+            Tool::BackgroundBrush,
+            &[cx as f32, cy as f32, size, water, pigment_amount, pigment as f32],
+        );
+        let slot = self.pigment_slot(pigment);
         let w = self.width as i32;
         let h = self.height as i32;
         let radius = size.max(1.0);
         let isize = radius.ceil() as i32;
+        self.wake_around(cx, cy, isize);
         let sigma = radius * 0.62;
         let sigma2 = sigma * sigma;
 
@@ -337,15 +1334,9 @@ impl WatercolorEngine {
                 let paper_val = self.paper_h[idx];
                 let paper_response = 0.7 + 0.3 * (1.0 - paper_val);
                 let gaussian = (-dist_sq / (2.0 * sigma2)).exp();
-                let existing = (self.dr[idx]
-                    + self.dg[idx]
-                    + self.db[idx]
-                    + self.gr[idx] * 0.7
-                    + self.gg[idx] * 0.7
-                    + self.gb[idx] * 0.7)
-                    .min(1.5);
+                let existing = self.pigment_load(idx, 0.7).min(1.5);
                 // 이미 칠한 전경은 최대한 보호하고, 빈 종이 위주로 배경색을 깔아준다.
-                let background_allow = (1.0 - existing * 1.3).max(0.0).min(1.0);
+                let background_allow = (1.0 - existing * 1.3).clamp(0.0, 1.0);
                 if background_allow <= 0.001 {
                     continue;
                 }
@@ -353,14 +1344,16 @@ impl WatercolorEngine {
                 let factor = gaussian * feather * paper_response * background_allow;
                 self.h[idx] += water * factor * 0.28;
                 let pig = pigment_amount * factor * 0.26;
-                self.gr[idx] += (1.0 - r) * pig;
-                self.gg[idx] += (1.0 - g) * pig;
-                self.gb[idx] += (1.0 - b) * pig;
+                if let Some(slot) = slot {
+                    self.g[slot][idx] += pig;
+                }
                 self.mask[idx] = (self.mask[idx] + 0.22 * factor).min(1.0);
             }
         }
+        self.close_stroke(scope);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_background_brush_stroke(
         &mut self,
         x0: i32,
@@ -370,10 +1363,21 @@ impl WatercolorEngine {
         size: f32,
         water: f32,
         pigment_amount: f32,
-        r: f32,
-        g: f32,
-        b: f32,
+        pigment: u32,
     ) {
+        let scope = self.open_stroke(
+            Tool::BackgroundBrushStroke,
+            &[
+                x0 as f32,
+                y0 as f32,
+                x1 as f32,
+                y1 as f32,
+                size,
+                water,
+                pigment_amount,
+                pigment as f32,
+            ],
+        );
         let dx = (x1 - x0) as f32;
         let dy = (y1 - y0) as f32;
         let length = (dx * dx + dy * dy).sqrt();
@@ -384,10 +1388,12 @@ impl WatercolorEngine {
             let t = s as f32 / steps as f32;
             let x = x0 as f32 + dx * t;
             let y = y0 as f32 + dy * t;
-            self.apply_background_brush(x as i32, y as i32, size, water, pigment_amount, r, g, b);
+            self.apply_background_brush(x as i32, y as i32, size, water, pigment_amount, pigment);
         }
+        self.close_stroke(scope);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_fade_brush_stroke(
         &mut self,
         x0: i32,
@@ -398,11 +1404,15 @@ impl WatercolorEngine {
         fade_strength: f32,
         velocity: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::FadeBrushStroke,
+            &[x0 as f32, y0 as f32, x1 as f32, y1 as f32, size, fade_strength, velocity],
+        );
         let dx = (x1 - x0) as f32;
         let dy = (y1 - y0) as f32;
         let length = (dx * dx + dy * dy).sqrt();
         let angle = dy.atan2(dx);
-        let pressure = (1.0 / (1.0 + velocity * 0.08)).max(0.2).min(1.0);
+        let pressure = (1.0 / (1.0 + velocity * 0.08)).clamp(0.2, 1.0);
         let step_size = (size * 0.3).max(0.5);
         let steps = (length / step_size).ceil().max(1.0) as i32;
 
@@ -422,8 +1432,10 @@ impl WatercolorEngine {
                 pressure * attenuation,
             );
         }
+        self.close_stroke(scope);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_blend_brush_stroke(
         &mut self,
         x0: i32,
@@ -434,11 +1446,15 @@ impl WatercolorEngine {
         blend_strength: f32,
         velocity: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::BlendBrushStroke,
+            &[x0 as f32, y0 as f32, x1 as f32, y1 as f32, size, blend_strength, velocity],
+        );
         let dx = (x1 - x0) as f32;
         let dy = (y1 - y0) as f32;
         let length = (dx * dx + dy * dy).sqrt();
         let angle = dy.atan2(dx);
-        let pressure = (1.0 / (1.0 + velocity * 0.08)).max(0.2).min(1.0);
+        let pressure = (1.0 / (1.0 + velocity * 0.08)).clamp(0.2, 1.0);
         let step_size = (size * 0.3).max(0.5);
         let steps = (length / step_size).ceil().max(1.0) as i32;
 
@@ -458,6 +1474,7 @@ impl WatercolorEngine {
                 pressure * attenuation,
             );
         }
+        self.close_stroke(scope);
     }
 
     pub fn apply_silhouette_blend_brush(
@@ -469,23 +1486,24 @@ impl WatercolorEngine {
         angle: f32,
         pressure: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::SilhouetteBlendBrush,
+            &[cx as f32, cy as f32, size, blend_strength, angle, pressure],
+        );
         let w = self.width as i32;
         let h = self.height as i32;
         let radius = size.max(0.5);
         let isize = radius.ceil() as i32;
+        self.wake_around(cx, cy, isize);
         let sigma = radius * 0.45;
         let sigma2 = sigma * sigma;
         let cos_a = angle.cos();
         let sin_a = angle.sin();
-        let aspect = 0.7;
-        let strength = blend_strength.max(0.0).min(1.0);
+        let strength = blend_strength.clamp(0.0, 1.0);
 
-        let src_gr = self.gr.clone();
-        let src_gg = self.gg.clone();
-        let src_gb = self.gb.clone();
-        let src_dr = self.dr.clone();
-        let src_dg = self.dg.clone();
-        let src_db = self.db.clone();
+        let n = self.palette.len();
+        let src_g = self.g.clone();
+        let src_d = self.d.clone();
 
         for di in -isize..=isize {
             for dj in -isize..=isize {
@@ -505,7 +1523,7 @@ impl WatercolorEngine {
                 let fi = di as f32;
                 let fj = dj as f32;
                 let rot_x = fi * cos_a + fj * sin_a;
-                let rot_y = (-fi * sin_a + fj * cos_a) / aspect;
+                let rot_y = (-fi * sin_a + fj * cos_a) / BRUSH_ASPECT;
                 let dist_sq = rot_x * rot_x + rot_y * rot_y;
                 let dist = dist_sq.sqrt();
                 if dist > radius {
@@ -514,17 +1532,13 @@ impl WatercolorEngine {
 
                 let gaussian = (-dist_sq / (2.0 * sigma2)).exp();
                 let sil_weight = smoothstep(0.12, 0.6, silhouette);
-                let blend = (strength * gaussian * pressure * sil_weight).max(0.0).min(0.9);
+                let blend = (strength * gaussian * pressure * sil_weight).clamp(0.0, 0.9);
                 if blend <= 0.001 {
                     continue;
                 }
 
-                let mut sum_gr = 0.0;
-                let mut sum_gg = 0.0;
-                let mut sum_gb = 0.0;
-                let mut sum_dr = 0.0;
-                let mut sum_dg = 0.0;
-                let mut sum_db = 0.0;
+                let mut sum_g = [0.0f32; MAX_PIGMENTS];
+                let mut sum_d = [0.0f32; MAX_PIGMENTS];
                 let mut weight_sum = 0.0;
 
                 for ny in (ty - 1)..=(ty + 1) {
@@ -535,12 +1549,10 @@ impl WatercolorEngine {
                             continue;
                         }
                         let nw = smoothstep(0.12, 0.6, ns);
-                        sum_gr += src_gr[nidx] * nw;
-                        sum_gg += src_gg[nidx] * nw;
-                        sum_gb += src_gb[nidx] * nw;
-                        sum_dr += src_dr[nidx] * nw;
-                        sum_dg += src_dg[nidx] * nw;
-                        sum_db += src_db[nidx] * nw;
+                        for k in 0..n {
+                            sum_g[k] += src_g[k][nidx] * nw;
+                            sum_d[k] += src_d[k][nidx] * nw;
+                        }
                         weight_sum += nw;
                     }
                 }
@@ -549,25 +1561,18 @@ impl WatercolorEngine {
                     continue;
                 }
                 let inv = 1.0 / weight_sum;
-                let avg_gr = sum_gr * inv;
-                let avg_gg = sum_gg * inv;
-                let avg_gb = sum_gb * inv;
-                let avg_dr = sum_dr * inv;
-                let avg_dg = sum_dg * inv;
-                let avg_db = sum_db * inv;
-
-                self.gr[idx] = self.gr[idx] * (1.0 - blend) + avg_gr * blend;
-                self.gg[idx] = self.gg[idx] * (1.0 - blend) + avg_gg * blend;
-                self.gb[idx] = self.gb[idx] * (1.0 - blend) + avg_gb * blend;
-                self.dr[idx] = self.dr[idx] * (1.0 - blend) + avg_dr * blend;
-                self.dg[idx] = self.dg[idx] * (1.0 - blend) + avg_dg * blend;
-                self.db[idx] = self.db[idx] * (1.0 - blend) + avg_db * blend;
+                for k in 0..n {
+                    self.g[k][idx] = self.g[k][idx] * (1.0 - blend) + sum_g[k] * inv * blend;
+                    self.d[k][idx] = self.d[k][idx] * (1.0 - blend) + sum_d[k] * inv * blend;
+                }
                 self.h[idx] += blend * 0.03;
                 self.mask[idx] = 1.0;
             }
         }
+        self.close_stroke(scope);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_silhouette_blend_brush_stroke(
         &mut self,
         x0: i32,
@@ -578,11 +1583,15 @@ impl WatercolorEngine {
         blend_strength: f32,
         velocity: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::SilhouetteBlendBrushStroke,
+            &[x0 as f32, y0 as f32, x1 as f32, y1 as f32, size, blend_strength, velocity],
+        );
         let dx = (x1 - x0) as f32;
         let dy = (y1 - y0) as f32;
         let length = (dx * dx + dy * dy).sqrt();
         let angle = dy.atan2(dx);
-        let pressure = (1.0 / (1.0 + velocity * 0.08)).max(0.2).min(1.0);
+        let pressure = (1.0 / (1.0 + velocity * 0.08)).clamp(0.2, 1.0);
         let step_size = (size * 0.3).max(0.5);
         let steps = (length / step_size).ceil().max(1.0) as i32;
 
@@ -602,8 +1611,10 @@ impl WatercolorEngine {
                 pressure * attenuation,
             );
         }
+        self.close_stroke(scope);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_water_brush_stroke(
         &mut self,
         x0: i32,
@@ -615,11 +1626,24 @@ impl WatercolorEngine {
         flow_strength: f32,
         velocity: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::WaterBrushStroke,
+            &[
+                x0 as f32,
+                y0 as f32,
+                x1 as f32,
+                y1 as f32,
+                size,
+                water_amount,
+                flow_strength,
+                velocity,
+            ],
+        );
         let dx = (x1 - x0) as f32;
         let dy = (y1 - y0) as f32;
         let length = (dx * dx + dy * dy).sqrt();
         let angle = dy.atan2(dx);
-        let pressure = (1.0 / (1.0 + velocity * 0.08)).max(0.2).min(1.0);
+        let pressure = (1.0 / (1.0 + velocity * 0.08)).clamp(0.2, 1.0);
         let step_size = (size * 0.3).max(0.5);
         let steps = (length / step_size).ceil().max(1.0) as i32;
 
@@ -640,74 +1664,427 @@ impl WatercolorEngine {
                 pressure * attenuation,
             );
         }
+        self.close_stroke(scope);
     }
 
     pub fn step(&mut self) {
+        // 하위 단계 하나에 물과 섬유 수분은 한 칸까지 번지므로, 넉넉히 그 두 배 안의 타일을 함께 돌린다
+        self.tiles.plan(2 * self.scale.ceil() as usize);
         self.update_velocities();
         self.relax_divergence();
-        self.move_fluid();
-        self.deposition();
-        self.capillary_flow();
-        self.back_run();
+        self.divergence_residual = self.measure_divergence();
+        self.apply_hydrostatic();
+        self.flow_outward();
+        // 배율 캔버스는 속도가 셀 단위로 scale배 빠르므로 옮기고 번지는 과정을 scale번에 나누어 진행한다
+        for _ in 0..self.scale as usize {
+            self.move_fluid();
+            self.bloom();
+            self.settle_pigment();
+            self.deposition();
+            self.capillary_flow();
+        }
+        self.rest_tiles();
+        self.free_slots = None;
+        self.journal.tick();
     }
 
+    // 캔버스 전체를 다시 칠해 복사본을 돌려준다 (내보내기, 명령줄 렌더러)
     pub fn render(&mut self) -> Vec<u8> {
-        for i in 0..self.total {
-            let offset = i * 4;
-            let total_r = self.dr[i] + self.gr[i] * 0.4;
-            let total_g = self.dg[i] + self.gg[i] * 0.4;
-            let total_b = self.db[i] + self.gb[i] * 0.4;
-
-            let mut out_r = (1.0 - total_r).max(0.0).min(1.0);
-            let mut out_g = (1.0 - total_g).max(0.0).min(1.0);
-            let mut out_b = (1.0 - total_b).max(0.0).min(1.0);
-
-            let wetness = self.h[i].min(1.0);
-            if wetness > 0.05 {
-                let gloss = 1.0 + wetness * 0.08;
-                out_r = (out_r * gloss).min(1.0);
-                out_g = (out_g * gloss).min(1.0);
-                out_b = (out_b * gloss).min(1.0);
-            }
+        self.tiles.touch_all();
+        self.render_frame();
+        self.pixels.clone()
+    }
 
-            if self.show_texture {
-                let paper = self.paper_render[i];
-                let has_paint = (total_r + total_g + total_b).min(1.0);
-                // 칠해진 영역일수록 텍스처 대비를 줄여 가이드 라인 잔상을 완화
-                let tex_fade = (1.0 - has_paint * 0.85).max(0.12);
-                let tex_strength = (0.03 + self.granularity * 0.08) * tex_fade;
-                // 0.5 중심 대비 방식으로 밝은 라인 편향을 줄임
-                let tex = 1.0 + (paper - 0.5) * tex_strength * 1.8;
-                out_r *= tex;
-                out_g *= tex;
-                out_b *= tex;
+    // 마지막 렌더 뒤 바뀐 타일만 다시 칠해 엔진의 픽셀 버퍼에 쓴다 (복사하지 않음).
+    // 화면은 pixels_ptr()/pixels_len()으로 버퍼를 직접 보고 take_dirty_rect() 영역만 올리면 된다.
+    pub fn render_frame(&mut self) {
+        let w = self.width;
+        let spans = self.tiles.take_dirty();
+        let mut pixels = std::mem::take(&mut self.pixels);
+        let this = &*self;
+        rows::for_each_row(&mut pixels, w * 4, |y, row| {
+            for &(x0, x1) in &spans[y / TILE] {
+                for (n, px) in row[x0 * 4..x1 * 4].chunks_mut(4 * LANES).enumerate() {
+                    this.shade(y * w + x0 + n * LANES, px);
+                }
             }
+        });
+        self.pixels = pixels;
+    }
+
+    // RGBA 픽셀 버퍼의 주소와 길이 (WASM 선형 메모리 안). 버퍼는 엔진이 살아 있는 동안 옮겨지지 않지만,
+    // 메모리가 커지면 JS 쪽 ArrayBuffer가 바뀌므로 뷰는 프레임마다 새로 만든다.
+    pub fn pixels_ptr(&self) -> *const u8 {
+        self.pixels.as_ptr()
+    }
+
+    pub fn pixels_len(&self) -> usize {
+        self.pixels.len()
+    }
 
-            self.pixels[offset] = (out_r * 255.0).min(255.0).max(0.0) as u8;
-            self.pixels[offset + 1] = (out_g * 255.0).min(255.0).max(0.0) as u8;
-            self.pixels[offset + 2] = (out_b * 255.0).min(255.0).max(0.0) as u8;
-            self.pixels[offset + 3] = 255;
+    // 마지막으로 부른 뒤 다시 칠한 영역 [x, y, 너비, 높이]. 바뀐 곳이 없으면 너비/높이가 0이다.
+    pub fn take_dirty_rect(&mut self) -> Vec<u32> {
+        match self.tiles.take_changed() {
+            Some((x0, y0, x1, y1)) => {
+                vec![x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32]
+            }
+            None => vec![0; 4],
         }
-        self.pixels.clone()
+    }
+
+    // i부터 이어진 셀(최대 LANES개)의 화면 색을 RGBA로 쓴다
+    fn shade(&self, i: usize, px: &mut [u8]) {
+        let n = px.len() / 4;
+        let load = |src: &[f32]| {
+            if n == LANES {
+                F32x4::load(&src[i..])
+            } else {
+                F32x4::load_partial(&src[i..i + n])
+            }
+        };
+        let zero = F32x4::splat(0.0);
+        let one = F32x4::splat(1.0);
+
+        // 침착층(종이에 붙은 안료)과 부유층(물 속 안료)을 각각 KM 박막으로 보고
+        // 종이 -> 침착층 -> 부유층 순서로 합성한다.
+        let mut deposited = [[zero; 3]; 2];
+        let mut suspended = [[zero; 3]; 2];
+        let mut paint_amount = zero;
+        // 안료가 전혀 없는 층은 R = 0, T = 1이므로 KM 계산을 건너뛴다 (결과는 같다)
+        let (mut has_dep, mut has_sus) = (zero, zero);
+        for (slot, &id) in self.palette.iter().enumerate() {
+            let Some(pig) = self.pigments.get(id) else {
+                continue;
+            };
+            let d = load(&self.d[slot]);
+            let g = load(&self.g[slot]);
+            paint_amount = paint_amount.add(d.add(g.mul(F32x4::splat(0.4))));
+            let dep = d.max(zero).mul(F32x4::splat(PIGMENT_THICKNESS));
+            let sus = g
+                .max(zero)
+                .mul(F32x4::splat(PIGMENT_THICKNESS))
+                .mul(F32x4::splat(SUSPENDED_THICKNESS));
+            has_dep = has_dep.or(zero.lt(dep));
+            has_sus = has_sus.or(zero.lt(sus));
+            for c in 0..3 {
+                let (k, s) = (F32x4::splat(pig.ks.k[c]), F32x4::splat(pig.ks.s[c]));
+                deposited[0][c] = deposited[0][c].add(k.mul(dep));
+                deposited[1][c] = deposited[1][c].add(s.mul(dep));
+                suspended[0][c] = suspended[0][c].add(k.mul(sus));
+                suspended[1][c] = suspended[1][c].add(s.mul(sus));
+            }
+        }
+        let paper = F32x4::splat(PAPER_REFLECTANCE);
+        let (has_dep, has_sus) = (has_dep.any(), has_sus.any());
+        let mut out = [zero; 3];
+        for (c, o) in out.iter_mut().enumerate() {
+            let (rd, td) = if has_dep {
+                km::layer_rt4(deposited[0][c], deposited[1][c])
+            } else {
+                (zero, one)
+            };
+            let (rs, ts) = if has_sus {
+                km::layer_rt4(suspended[0][c], suspended[1][c])
+            } else {
+                (zero, one)
+            };
+            let (r_dep, _) = km::composite4(rd, td, paper, zero);
+            let (r_all, _) = km::composite4(rs, ts, r_dep, zero);
+            *o = r_all.max(zero).min(one);
+        }
+
+        // 섬유에 스민 물은 종이를 약간 어둡게 보이게 한다
+        let damp = one.sub(
+            load(&self.s)
+                .div(F32x4::splat(CAPACITY_MAX))
+                .min(one)
+                .mul(F32x4::splat(0.05)),
+        );
+        let wetness = load(&self.h).min(one);
+        let glossy = F32x4::splat(0.05).lt(wetness);
+        let gloss = one.add(wetness.mul(F32x4::splat(0.08)));
+        for o in out.iter_mut() {
+            *o = o.mul(damp);
+            *o = glossy.select(o.mul(gloss).min(one), *o);
+        }
+
+        if self.show_texture {
+            let has_paint = paint_amount.max(zero).min(one);
+            // 칠해진 영역일수록 텍스처 대비를 줄여 가이드 라인 잔상을 완화
+            let tex_fade = one
+                .sub(has_paint.mul(F32x4::splat(0.85)))
+                .max(F32x4::splat(0.12));
+            let tex_strength = F32x4::splat(0.03 + self.granularity * 0.08).mul(tex_fade);
+            // 0.5 중심 대비 방식으로 밝은 라인 편향을 줄임
+            let tex = one.add(
+                load(&self.paper_render)
+                    .sub(F32x4::splat(0.5))
+                    .mul(tex_strength)
+                    .mul(F32x4::splat(1.8)),
+            );
+            for o in out.iter_mut() {
+                *o = o.mul(tex);
+            }
+        }
+
+        let full = F32x4::splat(255.0);
+        let [r, g, b] = out.map(|o| o.mul(full).min(full).max(zero).to_array());
+        for (k, p) in px.chunks_exact_mut(4).enumerate() {
+            if self.debug_view != DEBUG_VIEW_NONE {
+                let v = self.debug_contribution(i + k);
+                let rgb = [1.0 - v.max(0.0), 1.0 - v.abs(), 1.0 + v.min(0.0)];
+                for (c, x) in rgb.into_iter().enumerate() {
+                    p[c] = (x * 255.0).clamp(0.0, 255.0) as u8;
+                }
+            } else {
+                p[..3].copy_from_slice(&[r[k] as u8, g[k] as u8, b[k] as u8]);
+            }
+            p[3] = 255;
+        }
+    }
+
+    // render() 결과를 PNG로 만든다. 네이티브/WASM 어디서나 같은 바이트가 나온다.
+    // metadata가 켜져 있으면 엔진 버전, 물리 설정, 붓 기록 해시를 tEXt 청크로 넣는다.
+    pub fn export_png(&mut self, metadata: bool) -> Vec<u8> {
+        let pixels = self.render();
+        let text = if metadata {
+            self.export_metadata()
+        } else {
+            Vec::new()
+        };
+        let text: Vec<(&str, &str)> = text.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        png::encode_rgba_with_text(self.width as u32, self.height as u32, &pixels, &text)
+    }
+
+    pub fn export_ppm(&mut self) -> Vec<u8> {
+        let pixels = self.render();
+        png::encode_ppm(self.width as u32, self.height as u32, &pixels)
     }
 
     pub fn reset(&mut self) {
+        let scope = self.open_stroke(Tool::Reset, &[]);
         self.h.iter_mut().for_each(|v| *v = 0.0);
         self.u.iter_mut().for_each(|v| *v = 0.0);
         self.v.iter_mut().for_each(|v| *v = 0.0);
         self.p.iter_mut().for_each(|v| *v = 0.0);
         self.mask.iter_mut().for_each(|v| *v = 0.0);
-        self.gr.iter_mut().for_each(|v| *v = 0.0);
-        self.gg.iter_mut().for_each(|v| *v = 0.0);
-        self.gb.iter_mut().for_each(|v| *v = 0.0);
-        self.dr.iter_mut().for_each(|v| *v = 0.0);
-        self.dg.iter_mut().for_each(|v| *v = 0.0);
-        self.db.iter_mut().for_each(|v| *v = 0.0);
+        self.s.iter_mut().for_each(|v| *v = 0.0);
+        self.g.clear();
+        self.d.clear();
+        self.palette.clear();
+        self.free_slots = None;
+        self.tiles.wake_all();
+        self.close_stroke(scope);
+    }
+
+    // 포인터를 누른 순간부터 뗄 때까지의 붓 호출을 되돌리기 한 번으로 묶는다
+    pub fn begin_stroke(&mut self) {
+        self.journal.record(|| Entry::Call(Tool::BeginStroke, Vec::new()));
+        if !self.stroke_open {
+            let edited = self.tiles.take_edited();
+            let (history, mut live) = self.history_fields();
+            history.record(&mut live, &edited);
+            self.stroke_open = true;
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.journal.record(|| Entry::Call(Tool::EndStroke, Vec::new()));
+        self.stroke_open = false;
+    }
+
+    // 마지막 붓 동작 직전으로 돌아간다. 젖은 물감과 흐름(속도, 압력)도 그때 상태로 복원된다.
+    pub fn undo(&mut self) -> bool {
+        self.journal.record(|| Entry::Call(Tool::Undo, Vec::new()));
+        self.stroke_open = false;
+        if !self.history.can_undo() {
+            return false;
+        }
+        let edited = self.tiles.take_edited();
+        let (history, mut live) = self.history_fields();
+        let touched = history.undo(&mut live, &edited).unwrap_or(edited);
+        self.restored(&touched);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.journal.record(|| Entry::Call(Tool::Redo, Vec::new()));
+        self.stroke_open = false;
+        if !self.history.can_redo() {
+            return false;
+        }
+        let edited = self.tiles.take_edited();
+        let (history, mut live) = self.history_fields();
+        let touched = history.redo(&mut live, &edited).unwrap_or(edited);
+        self.restored(&touched);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    // 작업 중인 그림을 통째로 저장한다 (젖은 물감, 흐름, 종이, 안료, 설정 포함. 되돌리기 기록은 제외)
+    pub fn save_state(&self) -> Vec<u8> {
+        state::save(self)
+    }
+
+    // 저장된 크기로 캔버스가 바뀔 수 있다. 실패하면 현재 상태는 그대로 유지된다.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        *self = state::load(data)?;
+        Ok(())
+    }
+
+    pub fn state_format_version() -> u32 {
+        state::FORMAT_VERSION as u32
+    }
+
+    // 지금 상태를 바탕으로 붓 기록을 새로 시작한다.
+    // 기록 이전의 되돌리기 기록은 재생할 수 없으므로 비운다.
+    pub fn start_journal(&mut self) {
+        self.stroke_open = false;
+        self.history = History::new(self.width, self.height, HISTORY_BYTES);
+        let base = self.save_state();
+        self.journal.start(base);
+    }
+
+    pub fn stop_journal(&mut self) {
+        self.journal.stop();
+    }
+
+    pub fn is_journaling(&self) -> bool {
+        self.journal.is_recording()
+    }
+
+    pub fn journal_len(&self) -> u32 {
+        self.journal.len() as u32
+    }
+
+    pub fn journal_steps(&self) -> u32 {
+        self.journal.steps()
+    }
+
+    pub fn export_journal(&self) -> Vec<u8> {
+        self.journal.encode()
+    }
+
+    // 붓 기록을 처음부터 다시 실행해 같은 그림을 만든다. 실패하면 현재 상태는 그대로 유지된다.
+    pub fn replay(&mut self, journal: &[u8]) -> Result<(), String> {
+        *self = journal::replay(journal, 1)?;
+        Ok(())
+    }
+
+    // 작게 칠한 붓 기록을 scale배(최대 MAX_REPLAY_SCALE) 해상도로 다시 그린다.
+    // 붓 위치/크기, step 수, dt가 배율에 맞게 바뀌어 흐름이 같은 모양으로 퍼진다.
+    pub fn replay_scaled(&mut self, journal: &[u8], scale: u32) -> Result<(), String> {
+        if scale == 0 || scale > MAX_REPLAY_SCALE {
+            return Err(format!("배율은 1~{} 사이여야 합니다", MAX_REPLAY_SCALE));
+        }
+        *self = journal::replay(journal, scale)?;
+        Ok(())
+    }
+
+    // replay_scaled로 키운 캔버스의 배율 (원래 셀 하나가 차지하는 셀 수)
+    pub fn resolution_scale(&self) -> f32 {
+        self.scale
     }
 }
 
 // === 내부 시뮬레이션 ===
 impl WatercolorEngine {
+    fn export_metadata(&self) -> Vec<(&'static str, String)> {
+        let mut text = vec![
+            (
+                "Software",
+                format!("watercolor-engine {}", env!("CARGO_PKG_VERSION")),
+            ),
+            (
+                "Physics",
+                format!(
+                    "dt={} evaporation={} viscosity={} pressure={} iterations={} \
+                     absorption={} diffusion={} bloom={} edge={} adhesion={} granularity={}",
+                    self.dt,
+                    self.evaporation,
+                    self.viscosity,
+                    self.pressure,
+                    self.iterations,
+                    self.absorption,
+                    self.capillary_diffusion,
+                    self.bloom_strength,
+                    self.edge_darkening,
+                    self.adhesion,
+                    self.granularity
+                ),
+            ),
+        ];
+        // 붓 기록이 있으면 export_journal() 바이트의 CRC-32로 그림과 기록을 짝지을 수 있다
+        if self.journal.is_started() {
+            let journal = self.journal.encode();
+            text.push(("Journal-CRC32", format!("{:08x}", png::crc32(&[&journal]))));
+            text.push(("Journal-Steps", self.journal.steps().to_string()));
+        }
+        text
+    }
+
+    // 붓 호출 하나를 되돌리기 단위로 열고 저널에 남긴다.
+    // begin_stroke()로 이미 열려 있으면 그 동작에 포함되고, 붓 호출 안의 중첩 호출은 기록하지 않는다.
+    fn open_stroke(&mut self, tool: Tool, args: &[f32]) -> CallScope {
+        let journaled = self.journal.enter(|| Entry::Call(tool, args.to_vec()));
+        let checkpoint = !self.stroke_open;
+        if checkpoint {
+            self.begin_stroke();
+        }
+        CallScope {
+            checkpoint,
+            journaled,
+        }
+    }
+
+    fn close_stroke(&mut self, scope: CallScope) {
+        if scope.checkpoint {
+            self.end_stroke();
+        }
+        self.journal.leave(scope.journaled);
+    }
+
+    fn add_pigment(&mut self, pigment: Pigment) -> u32 {
+        self.journal.record(|| Entry::Pigment(pigment.clone()));
+        let id = self.pigments.add(pigment);
+        // 같은 이름이면 이미 칠한 안료의 색이 바뀐다
+        if self.palette.contains(&id) {
+            self.tiles.touch_all();
+        }
+        id
+    }
+
+    // 되돌리기 기록이 다루는 필드
+    fn history_fields(&mut self) -> (&mut History, Fields<'_>) {
+        let live = Fields {
+            h: &mut self.h,
+            u: &mut self.u,
+            v: &mut self.v,
+            p: &mut self.p,
+            mask: &mut self.mask,
+            s: &mut self.s,
+            g: &mut self.g,
+            d: &mut self.d,
+            palette: &mut self.palette,
+        };
+        (&mut self.history, live)
+    }
+
+    // 되돌리기/다시하기로 값이 바뀐 타일을 깨운다
+    fn restored(&mut self, tiles: &[usize]) {
+        for &t in tiles {
+            self.tiles.wake_tile(t);
+        }
+        self.free_slots = None;
+    }
+
     fn silhouette_transport_factor(&self, idx: usize) -> f32 {
         if self.silhouette_strength <= 0.001 {
             return 1.0;
@@ -715,7 +2092,68 @@ impl WatercolorEngine {
         let sil = self.silhouette_map[idx];
         let base = 1.0 - self.silhouette_strength + self.silhouette_strength * sil;
         let fringe = (1.0 - sil) * self.silhouette_edge[idx] * self.edge_bleed_strength * 0.9;
-        (base + fringe).max(0.0).min(1.0)
+        (base + fringe).clamp(0.0, 1.0)
+    }
+
+    // 팔레트 슬롯별 들어올리기 쉬운 정도 (착색 안료는 거의 안 들리고 침전성 안료는 잘 들린다)
+    fn slot_liftability(&self) -> Vec<f32> {
+        self.palette
+            .iter()
+            .map(|&id| {
+                self.pigments
+                    .get(id)
+                    .map_or(1.0, |pig| (1.0 - pig.staining) * LIFT_GAIN)
+            })
+            .collect()
+    }
+
+    // 해당 픽셀의 전체 안료량 (침착 + 부유 * suspended_weight)
+    fn pigment_load(&self, idx: usize, suspended_weight: f32) -> f32 {
+        let mut sum = 0.0;
+        for slot in 0..self.palette.len() {
+            sum += self.d[slot][idx] + self.g[slot][idx] * suspended_weight;
+        }
+        sum
+    }
+
+    // 안료 id를 팔레트 슬롯에 배정하고 안료 평면을 만든다.
+    // 슬롯이 가득 차면 캔버스에서 사라진 (g, d가 모두 SLOT_EPS 미만인) 슬롯의 찌꺼기를 지우고 다시 쓰며,
+    // 그런 슬롯도 없으면 None. 슬롯 배정은 되돌리기 스냅샷에 함께 들어간다.
+    fn pigment_slot(&mut self, id: u32) -> Option<usize> {
+        self.pigments.get(id)?;
+        if let Some(slot) = self.palette.iter().position(|&p| p == id) {
+            // 다시 칠하므로 비울 후보에서 뺀다
+            if let Some(free) = &mut self.free_slots {
+                free.retain(|&f| f != slot);
+            }
+            return Some(slot);
+        }
+        if self.palette.len() < MAX_PIGMENTS {
+            self.palette.push(id);
+            self.g.push(vec![0.0; self.total]);
+            self.d.push(vec![0.0; self.total]);
+            return Some(self.palette.len() - 1);
+        }
+        if self.free_slots.is_none() {
+            self.free_slots = Some(self.spent_slots());
+        }
+        let free = self.free_slots.as_mut()?;
+        if free.is_empty() {
+            return None;
+        }
+        let slot = free.remove(0);
+        // 찌꺼기는 잠든 타일에도 있을 수 있으므로 모두 깨워 렌더와 되돌리기 기록이 보게 한다
+        self.g[slot].fill(0.0);
+        self.d[slot].fill(0.0);
+        self.tiles.wake_all();
+        self.palette[slot] = id;
+        Some(slot)
+    }
+
+    fn spent_slots(&self) -> Vec<usize> {
+        (0..self.palette.len())
+            .filter(|&slot| self.g[slot].iter().chain(&self.d[slot]).all(|&v| v < SLOT_EPS))
+            .collect()
     }
 
     fn rebuild_paper_render_map(&mut self) {
@@ -742,7 +2180,7 @@ impl WatercolorEngine {
                 }
                 let avg = sum / cnt.max(1.0);
                 let compressed = 0.5 + (avg - 0.5) * 0.35;
-                self.paper_render[i * w + j] = compressed.max(0.0).min(1.0);
+                self.paper_render[i * w + j] = compressed.clamp(0.0, 1.0);
             }
         }
     }
@@ -759,7 +2197,7 @@ impl WatercolorEngine {
                 let gx = self.silhouette_map[idx + 1] - self.silhouette_map[idx - 1];
                 let gy = self.silhouette_map[idx + w] - self.silhouette_map[idx - w];
                 let g = (gx * gx + gy * gy).sqrt();
-                self.silhouette_edge[idx] = (g * 3.0).max(0.0).min(1.0);
+                self.silhouette_edge[idx] = (g * 3.0).clamp(0.0, 1.0);
             }
         }
         for j in 0..w {
@@ -781,16 +2219,21 @@ impl WatercolorEngine {
         angle: f32,
         pressure: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::FadeBrush,
+            &[cx as f32, cy as f32, size, fade_strength, angle, pressure],
+        );
         let w = self.width as i32;
         let h = self.height as i32;
         let radius = size.max(0.5);
         let isize = radius.ceil() as i32;
+        self.wake_around(cx, cy, isize);
         let sigma = radius * 0.45;
         let sigma2 = sigma * sigma;
         let cos_a = angle.cos();
         let sin_a = angle.sin();
-        let aspect = 0.7;
-        let strength = fade_strength.max(0.0).min(1.0);
+        let strength = fade_strength.clamp(0.0, 1.0);
+        let liftable = self.slot_liftability();
 
         for di in -isize..=isize {
             for dj in -isize..=isize {
@@ -804,7 +2247,7 @@ impl WatercolorEngine {
                 let fi = di as f32;
                 let fj = dj as f32;
                 let rot_x = fi * cos_a + fj * sin_a;
-                let rot_y = (-fi * sin_a + fj * cos_a) / aspect;
+                let rot_y = (-fi * sin_a + fj * cos_a) / BRUSH_ASPECT;
                 let dist_sq = rot_x * rot_x + rot_y * rot_y;
                 let dist = dist_sq.sqrt();
                 if dist > radius {
@@ -817,20 +2260,19 @@ impl WatercolorEngine {
                 let wetness = self.h[idx].min(1.0);
                 let wet_boost = 1.0 + wetness * 0.6;
                 let fade = (strength * gaussian * paper_response * pressure * wet_boost * 0.5)
-                    .max(0.0)
-                    .min(0.75);
+                    .clamp(0.0, 0.75);
                 let keep = 1.0 - fade;
 
-                self.gr[idx] *= keep;
-                self.gg[idx] *= keep;
-                self.gb[idx] *= keep;
-                self.dr[idx] *= 1.0 - fade * 0.8;
-                self.dg[idx] *= 1.0 - fade * 0.8;
-                self.db[idx] *= 1.0 - fade * 0.8;
+                // 물 속 안료는 그대로 닦이지만, 침착된 안료는 착색력만큼 종이에 남는다
+                for (slot, &lift) in liftable.iter().enumerate() {
+                    self.g[slot][idx] *= keep;
+                    self.d[slot][idx] *= 1.0 - (fade * 0.8 * lift).min(0.95);
+                }
                 self.h[idx] *= 1.0 - fade * 0.25;
                 self.mask[idx] *= 1.0 - fade * 0.5;
             }
         }
+        self.close_stroke(scope);
     }
 
     pub fn apply_blend_brush(
@@ -842,23 +2284,24 @@ impl WatercolorEngine {
         angle: f32,
         pressure: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::BlendBrush,
+            &[cx as f32, cy as f32, size, blend_strength, angle, pressure],
+        );
         let w = self.width as i32;
         let h = self.height as i32;
         let radius = size.max(0.5);
         let isize = radius.ceil() as i32;
+        self.wake_around(cx, cy, isize);
         let sigma = radius * 0.45;
         let sigma2 = sigma * sigma;
         let cos_a = angle.cos();
         let sin_a = angle.sin();
-        let aspect = 0.7;
-        let strength = blend_strength.max(0.0).min(1.0);
+        let strength = blend_strength.clamp(0.0, 1.0);
 
-        let src_gr = self.gr.clone();
-        let src_gg = self.gg.clone();
-        let src_gb = self.gb.clone();
-        let src_dr = self.dr.clone();
-        let src_dg = self.dg.clone();
-        let src_db = self.db.clone();
+        let n = self.palette.len();
+        let src_g = self.g.clone();
+        let src_d = self.d.clone();
 
         for di in -isize..=isize {
             for dj in -isize..=isize {
@@ -872,7 +2315,7 @@ impl WatercolorEngine {
                 let fi = di as f32;
                 let fj = dj as f32;
                 let rot_x = fi * cos_a + fj * sin_a;
-                let rot_y = (-fi * sin_a + fj * cos_a) / aspect;
+                let rot_y = (-fi * sin_a + fj * cos_a) / BRUSH_ASPECT;
                 let dist_sq = rot_x * rot_x + rot_y * rot_y;
                 let dist = dist_sq.sqrt();
                 if dist > radius {
@@ -880,50 +2323,37 @@ impl WatercolorEngine {
                 }
 
                 let gaussian = (-dist_sq / (2.0 * sigma2)).exp();
-                let blend = (strength * gaussian * pressure).max(0.0).min(0.85);
+                let blend = (strength * gaussian * pressure).clamp(0.0, 0.85);
                 if blend <= 0.001 {
                     continue;
                 }
 
-                let mut sum_gr = 0.0;
-                let mut sum_gg = 0.0;
-                let mut sum_gb = 0.0;
-                let mut sum_dr = 0.0;
-                let mut sum_dg = 0.0;
-                let mut sum_db = 0.0;
+                let mut sum_g = [0.0f32; MAX_PIGMENTS];
+                let mut sum_d = [0.0f32; MAX_PIGMENTS];
                 let mut count: f32 = 0.0;
                 for ny in (ty - 1)..=(ty + 1) {
                     for nx in (tx - 1)..=(tx + 1) {
                         let nidx = ny as usize * self.width + nx as usize;
-                        sum_gr += src_gr[nidx];
-                        sum_gg += src_gg[nidx];
-                        sum_gb += src_gb[nidx];
-                        sum_dr += src_dr[nidx];
-                        sum_dg += src_dg[nidx];
-                        sum_db += src_db[nidx];
+                        for k in 0..n {
+                            sum_g[k] += src_g[k][nidx];
+                            sum_d[k] += src_d[k][nidx];
+                        }
                         count += 1.0;
                     }
                 }
                 let inv = 1.0 / count.max(1.0);
-                let avg_gr = sum_gr * inv;
-                let avg_gg = sum_gg * inv;
-                let avg_gb = sum_gb * inv;
-                let avg_dr = sum_dr * inv;
-                let avg_dg = sum_dg * inv;
-                let avg_db = sum_db * inv;
-
-                self.gr[idx] = self.gr[idx] * (1.0 - blend) + avg_gr * blend;
-                self.gg[idx] = self.gg[idx] * (1.0 - blend) + avg_gg * blend;
-                self.gb[idx] = self.gb[idx] * (1.0 - blend) + avg_gb * blend;
-                self.dr[idx] = self.dr[idx] * (1.0 - blend) + avg_dr * blend;
-                self.dg[idx] = self.dg[idx] * (1.0 - blend) + avg_dg * blend;
-                self.db[idx] = self.db[idx] * (1.0 - blend) + avg_db * blend;
+                for k in 0..n {
+                    self.g[k][idx] = self.g[k][idx] * (1.0 - blend) + sum_g[k] * inv * blend;
+                    self.d[k][idx] = self.d[k][idx] * (1.0 - blend) + sum_d[k] * inv * blend;
+                }
                 self.h[idx] += blend * 0.04;
                 self.mask[idx] = 1.0;
             }
         }
+        self.close_stroke(scope);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn apply_water_brush(
         &mut self,
         cx: i32,
@@ -934,16 +2364,23 @@ impl WatercolorEngine {
         angle: f32,
         pressure: f32,
     ) {
+        let scope = self.open_stroke(
+            Tool::WaterBrush,
+            &[cx as f32, cy as f32, size, water_amount, flow_strength, angle, pressure],
+        );
         let w = self.width as i32;
         let h = self.height as i32;
         let radius = size.max(0.5);
         let isize = radius.ceil() as i32;
+        self.wake_around(cx, cy, isize);
         let sigma = radius * 0.45;
         let sigma2 = sigma * sigma;
         let cos_a = angle.cos();
         let sin_a = angle.sin();
-        let aspect = 0.7;
-        let flow = flow_strength.max(0.0).min(2.0);
+        let flow = flow_strength.clamp(0.0, 2.0);
+        // 붓이 밀어내는 속도는 셀 단위이므로 배율 캔버스에서는 그만큼 빠르다
+        let push = flow * 0.04 * self.scale;
+        let liftable = self.slot_liftability();
 
         for di in -isize..=isize {
             for dj in -isize..=isize {
@@ -957,7 +2394,7 @@ impl WatercolorEngine {
                 let fi = di as f32;
                 let fj = dj as f32;
                 let rot_x = fi * cos_a + fj * sin_a;
-                let rot_y = (-fi * sin_a + fj * cos_a) / aspect;
+                let rot_y = (-fi * sin_a + fj * cos_a) / BRUSH_ASPECT;
                 let dist_sq = rot_x * rot_x + rot_y * rot_y;
                 let dist = dist_sq.sqrt();
                 if dist > radius {
@@ -972,208 +2409,1439 @@ impl WatercolorEngine {
                 let transport = self.silhouette_transport_factor(idx);
                 let damp = 0.2 + 0.8 * transport;
                 self.h[idx] += add_water * damp;
-                self.u[idx] += radial_x * flow * edge * 0.04 * damp;
-                self.v[idx] += radial_y * flow * edge * 0.04 * damp;
-
-                let lift = (gaussian * flow * 0.06).min(0.2);
-                let move_r = self.dr[idx] * lift;
-                let move_g = self.dg[idx] * lift;
-                let move_b = self.db[idx] * lift;
-                self.dr[idx] -= move_r;
-                self.dg[idx] -= move_g;
-                self.db[idx] -= move_b;
-                self.gr[idx] += move_r;
-                self.gg[idx] += move_g;
-                self.gb[idx] += move_b;
+                self.add_cell_velocity(
+                    idx,
+                    radial_x * push * edge * damp,
+                    radial_y * push * edge * damp,
+                );
+
+                let lift = gaussian * flow * 0.06;
+                for (slot, &liftable) in liftable.iter().enumerate() {
+                    let moved = self.d[slot][idx] * (lift * liftable).min(0.3);
+                    self.d[slot][idx] -= moved;
+                    self.g[slot][idx] += moved;
+                }
                 self.mask[idx] = 1.0;
             }
         }
+        self.close_stroke(scope);
+    }
+
+    // 붓이 닿는 (cx, cy) 둘레 r칸의 타일을 깨운다. 경계면 속도는 이웃 셀에 걸치므로 한 칸 넓힌다.
+    fn wake_around(&mut self, cx: i32, cy: i32, r: i32) {
+        let (cx, cy, r) = (cx as isize, cy as isize, r as isize + 1);
+        self.tiles.wake(cx - r, cy - r, cx + r, cy + r);
+    }
+
+    // step이 끝나면 돌린 타일을 다시 본다.
+    // 물, 섬유 수분, 부유 안료, 압력, 둘레 경계면 속도가 모두 정확히 0인 타일만 재우고 나머지는 깨운다.
+    fn rest_tiles(&mut self) {
+        let w = self.width;
+        let uw = w + 1;
+        for (t, x0, y0, x1, y1) in self.tiles.covered() {
+            let busy = (y0..y1).any(|y| {
+                (x0..x1).any(|x| {
+                    let k = y * w + x;
+                    let ku = y * uw + x;
+                    self.h[k] != 0.0
+                        || self.s[k] != 0.0
+                        || self.p[k] != 0.0
+                        || self.u[ku] != 0.0
+                        || self.u[ku + 1] != 0.0
+                        || self.v[k] != 0.0
+                        || self.v[k + w] != 0.0
+                        || self.g.iter().any(|g| g[k] != 0.0)
+                })
+            });
+            if !busy {
+                // 잠든 동안 이웃 타일의 거리 변환이 읽으므로 마른 셀 값으로 둔다
+                for y in y0..y1 {
+                    self.wet_mask[y * w + x0..y * w + x1].fill(0.0);
+                    self.edge_dist[y * w + x0..y * w + x1].fill(0.0);
+                }
+            }
+            self.tiles.set_awake(t, busy);
+        }
+    }
+
+    // 표면에 물이 있거나 섬유가 충분히 젖은 셀은 표면 흐름이 들어갈 수 있다
+    fn is_wet(&self, idx: usize) -> bool {
+        self.h[idx] > WET_THRESHOLD || self.s[idx] > DAMP_THRESHOLD
+    }
+
+    // idx부터 n칸(LANES 이하)의 is_wet 마스크
+    fn is_wet4(&self, idx: usize, n: usize) -> F32x4 {
+        F32x4::splat(WET_THRESHOLD)
+            .lt(F32x4::load_n(&self.h[idx..], n))
+            .or(F32x4::splat(DAMP_THRESHOLD).lt(F32x4::load_n(&self.s[idx..], n)))
+    }
+
+    // 이송/번짐 하위 단계 하나의 시간 (step 하나를 scale번에 나누어 진행한다)
+    fn step_dt(&self) -> f32 {
+        self.dt / self.scale
+    }
+
+    // step마다 rate 비율로 줄던 양을 하위 단계 하나의 비율로 바꾼다
+    fn per_step(&self, rate: f32) -> f32 {
+        if self.scale == 1.0 {
+            return rate;
+        }
+        1.0 - (1.0 - rate.min(1.0)).powf(1.0 / self.scale)
+    }
+
+    // 이웃 셀 차이로 움직이는 항(정수압, 점성, 확산)은 셀이 1/scale로 작아지면 scale²배여야 같은 빠르기로 퍼진다
+    fn grid_gain(&self) -> f32 {
+        self.scale * self.scale
+    }
+
+    fn edge_range(&self) -> f32 {
+        EDGE_RANGE * self.scale
+    }
+
+    // 셀 중심 속도를 양쪽 경계면에 절반씩 나누어 더한다.
+    fn add_cell_velocity(&mut self, idx: usize, du: f32, dv: f32) {
+        let w = self.width;
+        let i = idx / w;
+        let j = idx % w;
+        let ku = i * (w + 1) + j;
+        self.u[ku] += du * 0.5;
+        self.u[ku + 1] += du * 0.5;
+        self.v[idx] += dv * 0.5;
+        self.v[idx + w] += dv * 0.5;
+    }
+
+    // 잠든 타일의 경계면 속도는 0이므로 돌리는 셀에 닿은 경계면만 본다
+    fn max_speed(&self) -> f32 {
+        let w = self.width;
+        let load = F32x4::load_n;
+        // 큰 값 고르기는 순서와 관계없으므로 4칸씩 모아 마지막에 합친다
+        let mut m = F32x4::splat(0.0);
+        for i in 0..self.height {
+            for &(x0, x1) in self.tiles.spans(i) {
+                let k = i * (w + 1);
+                for f in (x0..=x1).step_by(LANES) {
+                    m = m.max(load(&self.u[k + f..], LANES.min(x1 + 1 - f)).abs());
+                }
+            }
+            for (j, n) in self.tiles.col_chunks(i) {
+                m = m.max(load(&self.v[i * w + j..], n).abs());
+                m = m.max(load(&self.v[(i + 1) * w + j..], n).abs());
+            }
+        }
+        m.to_array().into_iter().fold(0.0, f32::max)
     }
 
+    // 셀 경계면 속도 갱신 (Curtis et al. 1997, UpdateVelocities)
+    // 이류(A)와 점성 확산(B)을 반영하고, 젖은 셀 사이가 아닌 경계면은 0으로 막아 젖은 영역 경계를 만든다.
     fn update_velocities(&mut self) {
         let w = self.width;
-        let friction = 1.0 - self.viscosity;
-        for i in 1..(self.height - 1) {
-            for j in 1..(w - 1) {
-                let idx = i * w + j;
-                if self.h[idx] < 0.001 {
-                    continue;
+        let h = self.height;
+        let uw = w + 1;
+        let mu = self.viscosity * self.grid_gain();
+        // 이류 CFL과 명시적 점성 확산의 안정 조건 (mu * dt * 4 <= 1)
+        let substeps = ((self.max_speed() * self.dt) / 0.5)
+            .max(mu * self.dt * 4.0)
+            .ceil()
+            .max(1.0)
+            .min(MAX_SUBSTEPS as f32 * self.scale) as usize;
+        let dt = self.dt / substeps as f32;
+
+        for _ in 0..substeps {
+            // 이전 값(u0, v0)만 읽고 자기 행만 쓰므로 행 단위로 나누어 돌릴 수 있다
+            let mut u0 = self.scratch.pop().unwrap_or_default();
+            let mut v0 = self.scratch.pop().unwrap_or_default();
+            self.tiles.copy(&self.u, uw, &mut u0);
+            self.tiles.copy(&self.v, w, &mut v0);
+            let mut u = std::mem::take(&mut self.u);
+            let mut v = std::mem::take(&mut self.v);
+            let this = &*self;
+            let splat = F32x4::splat;
+            // 경계면 하나씩 (캔버스 끝에서는 없는 이웃 대신 자기 값을 쓴다)
+            let face_u = |i: usize, f: usize| {
+                let l = i * w + f - 1;
+                let r = i * w + f;
+                let k = i * uw + f;
+                if !this.is_wet(l) || !this.is_wet(r) {
+                    return 0.0;
+                }
+                let up = if i > 0 { u0[k - uw] } else { u0[k] };
+                let down = if i + 1 < h { u0[k + uw] } else { u0[k] };
+                let uc_l = 0.5 * (u0[k - 1] + u0[k]);
+                let uc_r = 0.5 * (u0[k] + u0[k + 1]);
+                let uv_top = 0.5 * (up + u0[k]) * 0.5 * (v0[l] + v0[r]);
+                let uv_bottom = 0.5 * (u0[k] + down) * 0.5 * (v0[l + w] + v0[r + w]);
+                let adv = uc_l * uc_l - uc_r * uc_r + uv_top - uv_bottom;
+                let lap = u0[k - 1] + u0[k + 1] + up + down - 4.0 * u0[k];
+                u0[k] + dt * (adv + mu * lap)
+            };
+            let face_v = |i: usize, j: usize| {
+                let t = (i - 1) * w + j;
+                let b = i * w + j;
+                let k = b;
+                if !this.is_wet(t) || !this.is_wet(b) {
+                    return 0.0;
                 }
-                let dhdx = (self.h[idx + 1] + self.paper_h[idx + 1])
-                    - (self.h[idx - 1] + self.paper_h[idx - 1]);
-                let dhdy = (self.h[idx + w] + self.paper_h[idx + w])
-                    - (self.h[idx - w] + self.paper_h[idx - w]);
-                self.u[idx] += -1.5 * dhdx * self.dt;
-                self.v[idx] += -1.5 * dhdy * self.dt;
-                self.u[idx] *= friction;
-                self.v[idx] *= friction;
+                let left = if j > 0 { v0[k - 1] } else { v0[k] };
+                let right = if j + 1 < w { v0[k + 1] } else { v0[k] };
+                let vc_t = 0.5 * (v0[k - w] + v0[k]);
+                let vc_b = 0.5 * (v0[k] + v0[k + w]);
+                let uv_left = 0.5 * (u0[(i - 1) * uw + j] + u0[i * uw + j]) * 0.5 * (left + v0[k]);
+                let uv_right = 0.5 * (u0[(i - 1) * uw + j + 1] + u0[i * uw + j + 1])
+                    * 0.5
+                    * (v0[k] + right);
+                let adv = vc_t * vc_t - vc_b * vc_b + uv_left - uv_right;
+                let lap = v0[k - w] + v0[k + w] + left + right - 4.0 * v0[k];
+                v0[k] + dt * (adv + mu * lap)
+            };
+
+            rows::for_each_row(&mut u, uw, |i, row| {
+                let inner = i > 0 && i + 1 < h;
+                for &(x0, x1) in this.tiles.spans(i) {
+                    let (f0, f1) = (x0.max(1), (x1 + 1).min(w));
+                    let mut f = f0;
+                    while f < f1 {
+                        let l = i * w + f - 1;
+                        let r = i * w + f;
+                        let k = i * uw + f;
+                        // 4개 경계면씩 (캔버스 위/아래 행과 구간 끝은 하나씩)
+                        if inner && f + LANES <= f1 {
+                            let at = |src: &[f32], k: usize| F32x4::load(&src[k..]);
+                            let half = splat(0.5);
+                            let uk = at(&u0, k);
+                            let up = at(&u0, k - uw);
+                            let down = at(&u0, k + uw);
+                            let uc_l = half.mul(at(&u0, k - 1).add(uk));
+                            let uc_r = half.mul(uk.add(at(&u0, k + 1)));
+                            let uv_top = half
+                                .mul(up.add(uk))
+                                .mul(half)
+                                .mul(at(&v0, l).add(at(&v0, r)));
+                            let uv_bottom = half
+                                .mul(uk.add(down))
+                                .mul(half)
+                                .mul(at(&v0, l + w).add(at(&v0, r + w)));
+                            let adv = uc_l.mul(uc_l).sub(uc_r.mul(uc_r)).add(uv_top).sub(uv_bottom);
+                            let lap = at(&u0, k - 1)
+                                .add(at(&u0, k + 1))
+                                .add(up)
+                                .add(down)
+                                .sub(splat(4.0).mul(uk));
+                            let next = uk.add(splat(dt).mul(adv.add(splat(mu).mul(lap))));
+                            this.is_wet4(l, LANES)
+                                .and(this.is_wet4(r, LANES))
+                                .select(next, splat(0.0))
+                                .store(&mut row[f..]);
+                            f += LANES;
+                            continue;
+                        }
+                        row[f] = face_u(i, f);
+                        f += 1;
+                    }
+                }
+            });
+
+            rows::for_each_row(&mut v, w, |i, row| {
+                // 캔버스 위/아래 끝 경계면은 그대로 둔다
+                if i == 0 || i >= h {
+                    return;
+                }
+                for &(x0, x1) in this.tiles.spans(i) {
+                    let mut j = x0;
+                    while j < x1 {
+                        let t = (i - 1) * w + j;
+                        let b = i * w + j;
+                        let k = b;
+                        // 4칸씩 (캔버스 왼쪽/오른쪽 끝과 구간 끝은 하나씩)
+                        if j > 0 && j + LANES < w && j + LANES <= x1 {
+                            let at = |src: &[f32], k: usize| F32x4::load(&src[k..]);
+                            let half = splat(0.5);
+                            let vk = at(&v0, k);
+                            let left = at(&v0, k - 1);
+                            let right = at(&v0, k + 1);
+                            let vc_t = half.mul(at(&v0, k - w).add(vk));
+                            let vc_b = half.mul(vk.add(at(&v0, k + w)));
+                            let uv_left = half
+                                .mul(at(&u0, (i - 1) * uw + j).add(at(&u0, i * uw + j)))
+                                .mul(half)
+                                .mul(left.add(vk));
+                            let uv_right = half
+                                .mul(at(&u0, (i - 1) * uw + j + 1).add(at(&u0, i * uw + j + 1)))
+                                .mul(half)
+                                .mul(vk.add(right));
+                            let adv = vc_t.mul(vc_t).sub(vc_b.mul(vc_b)).add(uv_left).sub(uv_right);
+                            let lap = at(&v0, k - w)
+                                .add(at(&v0, k + w))
+                                .add(left)
+                                .add(right)
+                                .sub(splat(4.0).mul(vk));
+                            let next = vk.add(splat(dt).mul(adv.add(splat(mu).mul(lap))));
+                            this.is_wet4(t, LANES)
+                                .and(this.is_wet4(b, LANES))
+                                .select(next, splat(0.0))
+                                .store(&mut row[j..]);
+                            j += LANES;
+                            continue;
+                        }
+                        row[j] = face_v(i, j);
+                        j += 1;
+                    }
+                }
+            });
+            self.u = u;
+            self.v = v;
+            self.scratch.push(u0);
+            self.scratch.push(v0);
+        }
+
+        let friction = 1.0 - self.viscosity;
+        for i in 0..h {
+            for f in self.tiles.faces(i) {
+                self.u[i * uw + f] *= friction;
+            }
+            for j in self.tiles.cols(i) {
+                self.v[i * w + j] *= friction;
             }
         }
+        // 맨 아래 경계면은 아래 셀이 없으므로 마지막 행 셀을 따라간다
+        for j in self.tiles.cols(h - 1) {
+            self.v[h * w + j] *= friction;
+        }
     }
 
+    // 비압축 투영: 젖은 영역에서 ∇²p = ∇·u 를 red-black Gauss-Seidel로 풀고 u -= ∇p
+    // 마른 셀과 캔버스 밖은 벽(Neumann 경계)으로 취급한다.
     fn relax_divergence(&mut self) {
         let w = self.width;
-        for _ in 0..self.iterations {
-            for i in 1..(self.height - 1) {
-                for j in 1..(w - 1) {
+        let h = self.height;
+        let uw = w + 1;
+        // 돌리는 젖은 셀에만 쓰고 읽는다
+        let mut div = self.scratch.pop().unwrap_or_default();
+        div.resize(self.total, 0.0);
+        // 젖은 이웃 수 (마른 셀은 0). 반복하는 동안 바뀌지 않는다.
+        // 마른 셀의 압력은 여기서 0으로 두었거나 잠든 타일의 0이므로, 이웃 압력은 젖음을 보지 않고 더해도 된다.
+        let mut count = self.scratch.pop().unwrap_or_default();
+        count.resize(self.total, 0.0);
+        let splat = F32x4::splat;
+        for i in 0..h {
+            let inner = i > 0 && i + 1 < h;
+            for &(x0, x1) in self.tiles.spans(i) {
+                let mut j = x0;
+                while j < x1 {
                     let idx = i * w + j;
-                    let div = (self.u[idx + 1] - self.u[idx - 1] + self.v[idx + w]
-                        - self.v[idx - w])
-                        * 0.5;
-                    self.p[idx] -= div * self.pressure;
-                    if self.h[idx] > 0.01 {
-                        self.p[idx] -= self.evaporation * (1.0 - self.mask[idx]) * 5.0;
+                    // 안쪽 칸은 4칸씩
+                    if inner && j > 0 && j + LANES < w && j + LANES <= x1 {
+                        let wet = self.is_wet4(idx, LANES);
+                        let n = [idx - 1, idx + 1, idx - w, idx + w]
+                            .into_iter()
+                            .fold(splat(0.0), |n, l| {
+                                n.add(self.is_wet4(l, LANES).select(splat(1.0), splat(0.0)))
+                            });
+                        let ku = i * uw + j;
+                        let at = |src: &[f32], k: usize| F32x4::load(&src[k..]);
+                        at(&self.u, ku + 1)
+                            .sub(at(&self.u, ku))
+                            .add(at(&self.v, idx + w))
+                            .sub(at(&self.v, idx))
+                            .store(&mut div[idx..]);
+                        wet.select(n, splat(0.0)).store(&mut count[idx..]);
+                        wet.select(at(&self.p, idx), splat(0.0))
+                            .store(&mut self.p[idx..]);
+                        j += LANES;
+                        continue;
+                    }
+                    let col = j;
+                    j += 1;
+                    count[idx] = 0.0;
+                    if !self.is_wet(idx) {
+                        self.p[idx] = 0.0;
+                        continue;
                     }
+                    div[idx] = cell_divergence(&self.u, &self.v, w, idx);
+                    let mut n = 0.0f32;
+                    for (edge, l) in [
+                        (col > 0, idx.wrapping_sub(1)),
+                        (col + 1 < w, idx + 1),
+                        (i > 0, idx.wrapping_sub(w)),
+                        (i + 1 < h, idx + w),
+                    ] {
+                        if edge && self.is_wet(l) {
+                            n += 1.0;
+                        }
+                    }
+                    count[idx] = n;
                 }
             }
         }
+
+        // 이전 step의 압력으로 warm start
+        // 같은 색 셀은 다른 색 이웃만 읽으므로, 한 색을 풀 때 cur를 읽어 모든 돌리는 셀을 next에 쓰고 둘을 바꾼다.
+        // 자기 행만 쓰므로 행 단위 병렬이 가능하다. 돌리는 셀 밖(MARGIN)은 처음에 한 번 복사하면 바뀌지 않고,
+        // 한 번 반복에 두 번 바꾸므로 끝나면 cur가 다시 self.p 버퍼다.
+        let mut row_delta = vec![0.0f32; h];
+        let mut cur = std::mem::take(&mut self.p);
+        let mut next = self.scratch.pop().unwrap_or_default();
+        self.tiles.copy(&cur, w, &mut next);
+        for _ in 0..self.iterations {
+            row_delta.iter_mut().for_each(|x| *x = 0.0);
+            for color in 0..2 {
+                let tiles = &self.tiles;
+                let (prev, div, count) = (&cur, &div, &count);
+                rows::for_each_row2(&mut next, w, &mut row_delta, 1, |i, row, delta| {
+                    // 가장자리 칸: 캔버스 밖 이웃은 건너뛴다
+                    let cell = |row: &mut [f32], delta: &mut f32, j: usize| {
+                        let idx = i * w + j;
+                        let n = count[idx];
+                        row[j] = prev[idx];
+                        if (i + j) % 2 != color || n == 0.0 {
+                            return;
+                        }
+                        let mut sum = 0.0f32;
+                        if j > 0 {
+                            sum += prev[idx - 1];
+                        }
+                        if j + 1 < w {
+                            sum += prev[idx + 1];
+                        }
+                        if i > 0 {
+                            sum += prev[idx - w];
+                        }
+                        if i + 1 < h {
+                            sum += prev[idx + w];
+                        }
+                        let value = (sum - div[idx]) / n;
+                        *delta = delta.max((value - prev[idx]).abs());
+                        row[j] = value;
+                    };
+                    // 안쪽 칸은 4칸씩 (더하는 순서는 가장자리 칸과 같다)
+                    let inner = i > 0 && i + 1 < h;
+                    let zero = splat(0.0);
+                    let mut max_delta = zero;
+                    for &(x0, x1) in tiles.spans(i) {
+                        let mut j = x0;
+                        while j < x1 {
+                            if !inner || j == 0 || j + LANES >= w || j + LANES > x1 {
+                                cell(row, &mut delta[0], j);
+                                j += 1;
+                                continue;
+                            }
+                            let idx = i * w + j;
+                            let sum = [idx - 1, idx + 1, idx - w, idx + w]
+                                .into_iter()
+                                .fold(zero, |sum, l| sum.add(F32x4::load(&prev[l..])));
+                            let n = F32x4::load(&count[idx..]);
+                            let parity = if (i + j) % 2 == color {
+                                [1.0, 0.0, 1.0, 0.0]
+                            } else {
+                                [0.0, 1.0, 0.0, 1.0]
+                            };
+                            let update = zero.lt(F32x4::from_array(parity).mul(n));
+                            let old = F32x4::load(&prev[idx..]);
+                            let value = sum.sub(F32x4::load(&div[idx..])).div(n);
+                            max_delta = max_delta.max(update.select(value.sub(old).abs(), zero));
+                            update.select(value, old).store(&mut row[j..]);
+                            j += LANES;
+                        }
+                    }
+                    for d in max_delta.to_array() {
+                        delta[0] = delta[0].max(d);
+                    }
+                });
+                std::mem::swap(&mut cur, &mut next);
+            }
+            let max_delta = row_delta.iter().fold(0.0f32, |m, &x| m.max(x));
+            if max_delta < PRESSURE_TOLERANCE {
+                break;
+            }
+        }
+        self.p = cur;
+        self.scratch.extend([next, div, count]);
+
+        let load = F32x4::load_n;
+        for i in 0..h {
+            for (f, n) in self.tiles.inner_face_chunks(i) {
+                let (l, r, k) = (i * w + f - 1, i * w + f, i * uw + f);
+                let vel = load(&self.u[k..], n);
+                let grad = load(&self.p[r..], n).sub(load(&self.p[l..], n));
+                let wet = self.is_wet4(l, n).and(self.is_wet4(r, n));
+                wet.select(vel.sub(grad), vel).store_n(&mut self.u[k..], n);
+            }
+        }
+        for i in 1..h {
+            for (j, n) in self.tiles.col_chunks(i) {
+                let (t, b) = ((i - 1) * w + j, i * w + j);
+                let vel = load(&self.v[b..], n);
+                let grad = load(&self.p[b..], n).sub(load(&self.p[t..], n));
+                let wet = self.is_wet4(t, n).and(self.is_wet4(b, n));
+                wet.select(vel.sub(grad), vel).store_n(&mut self.v[b..], n);
+            }
+        }
     }
 
-    fn move_fluid(&mut self) {
+    // 수면 기울기(물 높이 + 종이 높이)에 의한 정수압 흐름
+    // 물이 얇게 퍼지며 높이가 고르게 되는 압축성 성분이므로 비압축 투영 뒤에 더한다.
+    fn apply_hydrostatic(&mut self) {
         let w = self.width;
         let h = self.height;
-        let mut next_h = vec![0.0f32; self.total];
-        let mut next_r = vec![0.0f32; self.total];
-        let mut next_g = vec![0.0f32; self.total];
-        let mut next_b = vec![0.0f32; self.total];
+        let uw = w + 1;
+        // pressure는 수면 기울기에 대한 정수압 강성 (기본 5.0 -> 1.5)
+        let impulse = self.pressure * 0.3 * self.dt * self.grid_gain();
+        let load = F32x4::load_n;
+        let impulse = F32x4::splat(impulse);
+        for i in 0..h {
+            for (f, n) in self.tiles.inner_face_chunks(i) {
+                let (l, r, k) = (i * w + f - 1, i * w + f, i * uw + f);
+                let level = |c: usize| load(&self.h[c..], n).add(load(&self.paper_h[c..], n));
+                let slope = level(r).sub(level(l));
+                let vel = load(&self.u[k..], n);
+                let wet = self.is_wet4(l, n).and(self.is_wet4(r, n));
+                wet.select(vel.sub(impulse.mul(slope)), vel)
+                    .store_n(&mut self.u[k..], n);
+            }
+        }
+        for i in 1..h {
+            for (j, n) in self.tiles.col_chunks(i) {
+                let (t, b) = ((i - 1) * w + j, i * w + j);
+                let level = |c: usize| load(&self.h[c..], n).add(load(&self.paper_h[c..], n));
+                let slope = level(b).sub(level(t));
+                let vel = load(&self.v[b..], n);
+                let wet = self.is_wet4(t, n).and(self.is_wet4(b, n));
+                wet.select(vel.sub(impulse.mul(slope)), vel)
+                    .store_n(&mut self.v[b..], n);
+            }
+        }
+    }
 
-        for i in 1..(h - 1) {
-            for j in 1..(w - 1) {
-                let idx = i * w + j;
-                if self.h[idx] <= 0.0 {
-                    continue;
+    // 젖은 영역 마스크와 마른 셀까지의 거리 (8-이웃 챔퍼 거리 변환, edge_range()에서 잘림)
+    fn update_wet_area(&mut self) {
+        let w = self.width;
+        let h = self.height;
+        let range = self.edge_range();
+        let splat = F32x4::splat;
+        let (depth, fiber, tiles) = (&self.h, &self.s, &self.tiles);
+        // 잠든 타일은 마른 셀뿐이라 마스크와 거리가 0으로 남아 있다 (rest_tiles)
+        rows::for_each_row2(&mut self.wet_mask, w, &mut self.edge_dist, w, |i, mask, dist| {
+            for &(x0, x1) in tiles.spans(i) {
+                for x in (x0..x1).step_by(LANES) {
+                    let n = LANES.min(x1 - x);
+                    let k = i * w + x;
+                    let wet = splat(WET_THRESHOLD)
+                        .lt(F32x4::load_partial(&depth[k..k + n]))
+                        .or(splat(DAMP_THRESHOLD).lt(F32x4::load_partial(&fiber[k..k + n])));
+                    wet.select(splat(1.0), splat(0.0))
+                        .store_partial(&mut mask[x..x + n]);
+                    wet.select(splat(range), splat(0.0))
+                        .store_partial(&mut dist[x..x + n]);
+                }
+            }
+        });
+        // 위에서 아래로 한 번, 아래에서 위로 한 번 훑는다.
+        // min은 순서와 관계없이 같은 값이므로, 이미 끝난 옆 행에서 오는 세 방향은 4칸씩 먼저 구하고
+        // 같은 행 안에서 이어지는 방향만 한 칸씩 따라간다.
+        let diag = std::f32::consts::SQRT_2;
+        // 캔버스 밖은 가장자리로 보지 않는다
+        let from_row = |dist: &mut [f32], i: usize, o: usize, j: usize| {
+            let k = i * w + j;
+            let mut d = dist[k];
+            if d != 0.0 {
+                d = d.min(dist[o * w + j] + 1.0);
+                if j > 0 {
+                    d = d.min(dist[o * w + j - 1] + diag);
+                }
+                if j + 1 < w {
+                    d = d.min(dist[o * w + j + 1] + diag);
                 }
+            }
+            dist[k] = d;
+        };
+        let from_row4 = |dist: &mut [f32], i: usize, o: usize, j: usize| {
+            let (k, l) = (i * w + j, o * w + j);
+            let d = F32x4::load(&dist[k..]);
+            let near = F32x4::load(&dist[l..])
+                .add(splat(1.0))
+                .min(F32x4::load(&dist[l - 1..]).add(splat(diag)))
+                .min(F32x4::load(&dist[l + 1..]).add(splat(diag)));
+            splat(0.0).lt(d).select(d.min(near), d).store(&mut dist[k..]);
+        };
+        let dist = &mut self.edge_dist;
+        for i in 0..h {
+            for &(x0, x1) in self.tiles.spans(i) {
+                if i > 0 {
+                    let mut j = x0;
+                    while j < x1 {
+                        if j > 0 && j + LANES < w && j + LANES <= x1 {
+                            from_row4(dist, i, i - 1, j);
+                            j += LANES;
+                        } else {
+                            from_row(dist, i, i - 1, j);
+                            j += 1;
+                        }
+                    }
+                }
+                for k in i * w + x0.max(1)..i * w + x1 {
+                    if dist[k] != 0.0 {
+                        dist[k] = dist[k].min(dist[k - 1] + 1.0);
+                    }
+                }
+            }
+        }
+        for i in (0..h).rev() {
+            for &(x0, x1) in self.tiles.spans(i).iter().rev() {
+                if i + 1 < h {
+                    let mut j = x0;
+                    while j < x1 {
+                        if j > 0 && j + LANES < w && j + LANES <= x1 {
+                            from_row4(dist, i, i + 1, j);
+                            j += LANES;
+                        } else {
+                            from_row(dist, i, i + 1, j);
+                            j += 1;
+                        }
+                    }
+                }
+                for k in (i * w + x0..(i * w + x1).min((i + 1) * w - 1)).rev() {
+                    if dist[k] != 0.0 {
+                        dist[k] = dist[k].min(dist[k + 1] + 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    // 가장자리 어두워짐 (Curtis et al. 1997, FlowOutward)
+    // 젖은 영역 가장자리는 증발이 빨라 안쪽 물이 바깥으로 끌려가고, 함께 실려 간 안료가 테두리에 쌓인다.
+    fn flow_outward(&mut self) {
+        self.update_wet_area();
+        if self.edge_darkening <= 0.0 {
+            return;
+        }
+        let w = self.width;
+        let h = self.height;
+        let uw = w + 1;
+        let impulse = self.edge_darkening * EDGE_FLOW * self.dt * self.grid_gain();
+        let range = self.edge_range();
+        let (splat, load) = (F32x4::splat, F32x4::load_n);
+        // 가장자리에서 1, edge_range() 안쪽으로 갈수록 0
+        let rim = |k: usize, n: usize| {
+            let d = load(&self.edge_dist[k..], n);
+            splat(1.0).sub(d.div(splat(range))).max(splat(0.0))
+        };
+        let wet = |k: usize, n: usize| splat(0.0).lt(load(&self.wet_mask[k..], n));
+        for i in 0..h {
+            for (f, n) in self.tiles.inner_face_chunks(i) {
+                let (l, r, k) = (i * w + f - 1, i * w + f, i * uw + f);
+                let vel = load(&self.u[k..], n);
+                let pull = vel.add(splat(impulse).mul(rim(r, n).sub(rim(l, n))));
+                wet(l, n)
+                    .and(wet(r, n))
+                    .select(pull, vel)
+                    .store_n(&mut self.u[k..], n);
+            }
+        }
+        for i in 1..h {
+            for (j, n) in self.tiles.col_chunks(i) {
+                let (t, b) = ((i - 1) * w + j, i * w + j);
+                let vel = load(&self.v[b..], n);
+                let pull = vel.add(splat(impulse).mul(rim(b, n).sub(rim(t, n))));
+                wet(t, n)
+                    .and(wet(b, n))
+                    .select(pull, vel)
+                    .store_n(&mut self.v[b..], n);
+            }
+        }
+        let extra = splat((self.evaporation * self.edge_darkening).min(0.5));
+        for i in 0..h {
+            for (j, n) in self.tiles.col_chunks(i) {
+                let k = i * w + j;
+                let water = load(&self.h[k..], n);
+                let dried = water.mul(splat(1.0).sub(extra.mul(rim(k, n))));
+                wet(k, n).select(dried, water).store_n(&mut self.h[k..], n);
+            }
+        }
+    }
 
-                let pi = (i as f32 - self.u[idx] * self.dt)
-                    .max(1.0)
-                    .min((h - 2) as f32);
-                let pj = (j as f32 - self.v[idx] * self.dt)
-                    .max(1.0)
-                    .min((w - 2) as f32);
-
-                let i0 = pi.floor() as usize;
-                let j0 = pj.floor() as usize;
-                let i1 = i0 + 1;
-                let j1 = j0 + 1;
-                let si = pi - i0 as f32;
-                let sj = pj - j0 as f32;
-
-                let w00 = (1.0 - si) * (1.0 - sj);
-                let w10 = si * (1.0 - sj);
-                let w01 = (1.0 - si) * sj;
-                let w11 = si * sj;
-
-                let idx00 = i0 * w + j0;
-                let idx10 = i1 * w + j0;
-                let idx01 = i0 * w + j1;
-                let idx11 = i1 * w + j1;
-
-                next_h[idx] = (self.h[idx00] * w00
-                    + self.h[idx10] * w10
-                    + self.h[idx01] * w01
-                    + self.h[idx11] * w11)
-                    * (1.0 - self.evaporation);
-                next_r[idx] = self.gr[idx00] * w00
-                    + self.gr[idx10] * w10
-                    + self.gr[idx01] * w01
-                    + self.gr[idx11] * w11;
-                next_g[idx] = self.gg[idx00] * w00
-                    + self.gg[idx10] * w10
-                    + self.gg[idx01] * w01
-                    + self.gg[idx11] * w11;
-                next_b[idx] = self.gb[idx00] * w00
-                    + self.gb[idx10] * w10
-                    + self.gb[idx01] * w01
-                    + self.gb[idx11] * w11;
+    fn measure_divergence(&self) -> f32 {
+        let mut sum = 0.0f32;
+        let mut count = 0usize;
+        for i in 0..self.height {
+            for j in self.tiles.cols(i) {
+                let idx = i * self.width + j;
+                if !self.is_wet(idx) {
+                    continue;
+                }
+                let div = cell_divergence(&self.u, &self.v, self.width, idx);
+                sum += div * div;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return 0.0;
+        }
+        (sum / count as f32).sqrt()
+    }
 
-                let transport = self.silhouette_transport_factor(idx);
-                let damp = 0.15 + transport * 0.85;
-                next_h[idx] *= damp;
-                next_r[idx] *= damp;
-                next_g[idx] *= damp;
-                next_b[idx] *= damp;
+    // 경계면 속도에 따른 풍상(upwind) 플럭스로 물과 부유 안료를 옮긴다.
+    // 셀에서 나간 양만큼 이웃에 더해지므로 총량이 보존된다.
+    fn move_fluid(&mut self) {
+        let w = self.width;
+        let h = self.height;
+        let uw = w + 1;
+        let substeps = ((self.max_speed() * self.step_dt()) / 0.25)
+            .ceil()
+            .max(1.0)
+            .min(MAX_SUBSTEPS as f32) as usize;
+        let dt = self.step_dt() / substeps as f32;
+
+        // 경계면마다 옮길 비율 (캔버스 가장자리 경계면은 쓰지 않는다)
+        // 속도가 0이 아닌 경계면은 양쪽 셀이 모두 돌리는 셀이므로 그 셀에 닿은 경계면만 구한다.
+        // 나머지 자리는 이전 내용이 남지만 속도가 0이라 쓰이지 않는다.
+        let mut transport = self.scratch.pop().unwrap_or_default();
+        let mut frac_u = self.scratch.pop().unwrap_or_default();
+        let mut frac_v = self.scratch.pop().unwrap_or_default();
+        transport.resize(self.total, 0.0);
+        frac_u.resize(self.u.len(), 0.0);
+        frac_v.resize(self.v.len(), 0.0);
+        for (y0, y1, x0, x1) in self.tiles.regions(h, w) {
+            for i in y0..y1 {
+                for j in x0..x1 {
+                    transport[i * w + j] = self.silhouette_transport_factor(i * w + j);
+                }
             }
         }
+        for i in 0..h {
+            for &(x0, x1) in self.tiles.spans(i) {
+                let (x0, x1) = (x0.max(1), x1.min(w - 1) + 1);
+                if x0 >= x1 {
+                    continue;
+                }
+                let (k, l) = (i * uw + x0, i * w + x0);
+                let n = x1 - x0;
+                face_fractions(
+                    &self.u[k..k + n],
+                    &transport[l - 1..l - 1 + n],
+                    &transport[l..l + n],
+                    dt,
+                    &mut frac_u[k..k + n],
+                );
+            }
+        }
+        for i in 1..h {
+            for &(x0, x1) in self.tiles.spans(i) {
+                let b = i * w + x0;
+                let n = x1 - x0;
+                face_fractions(
+                    &self.v[b..b + n],
+                    &transport[b - w..b - w + n],
+                    &transport[b..b + n],
+                    dt,
+                    &mut frac_v[b..b + n],
+                );
+            }
+        }
+        let faces = Faces {
+            u: &self.u,
+            v: &self.v,
+            frac_u: &frac_u,
+            frac_v: &frac_v,
+            tiles: &self.tiles,
+            w,
+            h,
+        };
+
+        // 셀마다 드나드는 양을 모아(gather) 자기 행만 쓴다.
+        // 더하는 순서가 경계면을 차례로 도는 것과 같아서 결과도 같다.
+        let mut src = self.scratch.pop().unwrap_or_default();
+        for _ in 0..substeps {
+            self.tiles.copy(&self.h, w, &mut src);
+            rows::for_each_row(&mut self.h, w, |i, row| faces.gather_row(&src, i, row));
+            for g in self.g.iter_mut() {
+                self.tiles.copy(g, w, &mut src);
+                rows::for_each_row(g, w, |i, row| faces.gather_row(&src, i, row));
+            }
+        }
+        self.scratch.extend([src, transport, frac_u, frac_v]);
 
-        self.h.copy_from_slice(&next_h);
-        self.gr.copy_from_slice(&next_r);
-        self.gg.copy_from_slice(&next_g);
-        self.gb.copy_from_slice(&next_b);
+        let keep = 1.0 - self.per_step(self.evaporation);
+        for i in 0..h {
+            for j in self.tiles.cols(i) {
+                self.h[i * w + j] *= keep;
+            }
+        }
     }
 
+
     fn deposition(&mut self) {
-        for i in 0..self.total {
-            if self.h[i] < 0.01 {
-                continue;
+        let adhesion_scale = self.adhesion / DEFAULT_ADHESION;
+        let w = self.width;
+        let uw = w + 1;
+        let splat = F32x4::splat;
+        // 안료와 무관한 두 인수는 한 번만 구한다: 빠른 물에서는 덜 가라앉고(flow_hold),
+        // 물이 깊을수록 안료가 떠 있고, 마르며 얇아질수록 빨리 가라앉는다(depth_hold)
+        let mut flow_hold = self.scratch.pop().unwrap_or_default();
+        let mut depth_hold = self.scratch.pop().unwrap_or_default();
+        flow_hold.resize(self.total, 0.0);
+        depth_hold.resize(self.total, 0.0);
+        let (h, u, v, tiles) = (&self.h, &self.u, &self.v, &self.tiles);
+        let scale = self.scale;
+        rows::for_each_row2(&mut flow_hold, w, &mut depth_hold, w, |y, flow, depth| {
+            for &(x0, x1) in tiles.spans(y) {
+                for x in (x0..x1).step_by(LANES) {
+                    let n = LANES.min(x1 - x);
+                    let i = y * w + x;
+                    let ku = y * uw + x;
+                    let load = |src: &[f32], at: usize| F32x4::load_partial(&src[at..at + n]);
+                    let cu = splat(0.5).mul(load(u, ku).add(load(u, ku + 1)));
+                    let cv = splat(0.5).mul(load(v, i).add(load(v, i + w)));
+                    // 속도는 원래 셀 단위로 환산
+                    let speed = cu.mul(cu).add(cv.mul(cv)).sqrt().div(splat(scale));
+                    splat(1.0)
+                        .div(speed.add(splat(0.5)))
+                        .store_partial(&mut flow[x..x + n]);
+                    splat(1.0)
+                        .div(splat(1.0).add(load(h, i).mul(splat(4.0))))
+                        .store_partial(&mut depth[x..x + n]);
+                }
             }
-            let speed = (self.u[i] * self.u[i] + self.v[i] * self.v[i]).sqrt();
-            let paper_val = self.paper_h[i];
-            let dep_rate = self.adhesion
-                * (1.0 / (speed + 0.5))
-                * (1.0 + self.granularity * (1.0 - paper_val) * 1.5);
-            let rate = (dep_rate * self.dt).min(0.5);
-            let (ar, ag, ab) = (self.gr[i] * rate, self.gg[i] * rate, self.gb[i] * rate);
-            self.dr[i] += ar;
-            self.dg[i] += ag;
-            self.db[i] += ab;
-            self.gr[i] -= ar;
-            self.gg[i] -= ag;
-            self.gb[i] -= ab;
+        });
+
+        for slot in 0..self.palette.len() {
+            let Some(pig) = self.pigments.get(self.palette[slot]) else {
+                continue;
+            };
+            let density = pig.density * adhesion_scale;
+            let granulation = self.granulation_weight(pig);
+            let flocculation = pig.flocculation;
+            let seed = self.palette[slot];
+            let floc_size = FLOC_SCALE * self.scale;
+            let step_dt = self.step_dt();
+            let paper_h = &self.paper_h;
+            let (flow_hold, depth_hold) = (&flow_hold, &depth_hold);
+            rows::for_each_row2(&mut self.g[slot], w, &mut self.d[slot], w, |y, g, d| {
+                let mut floc = None;
+                // 4칸씩 묶어 계산한다. 연산 순서가 칸 하나씩 계산하던 식과 같아 결과도 같다.
+                for &(x0, x1) in tiles.spans(y) {
+                    for x in (x0..x1).step_by(LANES) {
+                        let n = LANES.min(x1 - x);
+                        let i = y * w + x;
+                        let load = |src: &[f32], at: usize| {
+                            if n == LANES {
+                                F32x4::load(&src[at..])
+                            } else {
+                                F32x4::load_partial(&src[at..at + n])
+                            }
+                        };
+                        let hh = load(h, i);
+                        let gg = load(g, x);
+                        let dd = load(d, x);
+                        // 물이 다 마르면 남은 부유 안료는 모두 종이에 내려앉는다
+                        let dry = hh.le(splat(WET_THRESHOLD));
+                        // 물이 너무 얕은 칸은 그대로 둔다
+                        let settling = splat(0.01).le(hh);
+                        let (g_new, d_new) = if settling.any() {
+                            let valley = splat(1.0).sub(load(paper_h, i));
+                            // 덩어리 노이즈는 안료가 가라앉는 칸이 있는 행에서만 구한다.
+                            // 응집하지 않는 안료는 아래 인수가 노이즈와 관계없이 정확히 1이므로 구하지 않는다.
+                            let clump = if flocculation > 0.0 {
+                                floc.get_or_insert_with(|| FlocRow::new(y, w, seed, floc_size))
+                                    .at4(x, w)
+                            } else {
+                                splat(0.5)
+                            };
+                            let dep_rate = splat(density)
+                                .mul(load(flow_hold, i))
+                                .mul(load(depth_hold, i))
+                                .mul(splat(1.0).add(splat(granulation).mul(valley).mul(splat(1.5))))
+                                .mul(splat(1.0).add(
+                                    splat(flocculation).mul(clump.sub(splat(0.5))),
+                                ));
+                            let rate = dep_rate.mul(splat(step_dt)).min(splat(0.5));
+                            let amount = gg.mul(rate);
+                            (
+                                settling.select(gg.sub(amount), gg),
+                                settling.select(dd.add(amount), dd),
+                            )
+                        } else {
+                            (gg, dd)
+                        };
+                        let g_new = dry.select(splat(0.0), g_new);
+                        let d_new = dry.select(dd.add(gg), d_new);
+                        if n == LANES {
+                            g_new.store(&mut g[x..]);
+                            d_new.store(&mut d[x..]);
+                        } else {
+                            g_new.store_partial(&mut g[x..x + n]);
+                            d_new.store_partial(&mut d[x..x + n]);
+                        }
+                    }
+                }
+            });
         }
+        self.scratch.extend([flow_hold, depth_hold]);
     }
 
-    fn capillary_flow(&mut self) {
+    // 과립화 세기: 무거운 안료일수록 골짜기로 빨리 가라앉는다
+    fn granulation_weight(&self, pig: &Pigment) -> f32 {
+        pig.granulation * (pig.density / GRANULATION_DENSITY) * (self.granularity / DEFAULT_GRANULARITY)
+    }
+
+    // 부유 안료를 끌어당기는 지형: 과립화 안료는 종이 골짜기, 응집 안료는 자기 덩어리 쪽
+    fn settle_field(&self, slot: usize, out: &mut [f32]) -> bool {
+        let Some(pig) = self.pigments.get(self.palette[slot]) else {
+            return false;
+        };
+        let gran = self.granulation_weight(pig) * GRANULATION_RATE;
+        let floc = pig.flocculation * FLOCCULATION_RATE;
+        if gran <= 0.0 && floc <= 0.0 {
+            return false;
+        }
+        let seed = self.palette[slot];
+        let floc_size = FLOC_SCALE * self.scale;
+        let (w, paper_h, tiles) = (self.width, &self.paper_h, &self.tiles);
+        let splat = F32x4::splat;
+        // 안료는 물이 있는 셀 사이에서만 옮겨 가므로 돌리는 셀만 구한다
+        rows::for_each_row(out, w, |y, out| {
+            let mut noise = None;
+            for &(x0, x1) in tiles.spans(y) {
+                for x in (x0..x1).step_by(LANES) {
+                    let n = LANES.min(x1 - x);
+                    let ph = F32x4::load_partial(&paper_h[y * w + x..y * w + x + n]);
+                    // 응집하지 않는 안료는 덩어리 노이즈를 구하지 않는다 (0을 곱해 더해도 같은 값)
+                    let clump = if floc > 0.0 {
+                        noise
+                            .get_or_insert_with(|| FlocRow::new(y, w, seed, floc_size))
+                            .at4(x, w)
+                    } else {
+                        splat(0.0)
+                    };
+                    let field = splat(gran)
+                        .mul(splat(1.0).sub(ph))
+                        .add(splat(floc).mul(clump));
+                    field.store_partial(&mut out[x..x + n]);
+                }
+            }
+        });
+        true
+    }
+
+    // 과립화/응집: 물 속 안료가 낮은 쪽(골짜기/덩어리)으로 옮겨 간다. 총량은 보존된다.
+    // 셀마다 이웃과 주고받는 양을 모아(gather) 자기 행만 쓴다. 더하고 빼는 순서가
+    // 셀을 차례로 돌며 이웃에 나눠 주던 것과 같아서 결과도 같다:
+    // 위 셀과 왼쪽 셀에서 받고, 왼쪽/오른쪽/위/아래로 주고, 오른쪽 셀과 아래 셀에서 받는다.
+    fn settle_pigment(&mut self) {
         let w = self.width;
-        for i in 1..(self.height - 1) {
-            for j in 1..(w - 1) {
-                let idx = i * w + j;
-                if self.h[idx] < 0.02 {
-                    continue;
+        let h = self.height;
+        let mut field = self.scratch.pop().unwrap_or_default();
+        let mut src = self.scratch.pop().unwrap_or_default();
+        field.resize(self.total, 0.0);
+        let speed = self.step_dt() * self.grid_gain();
+        let splat = F32x4::splat;
+        for slot in 0..self.palette.len() {
+            if !self.settle_field(slot, &mut field) {
+                continue;
+            }
+            self.tiles.copy(&self.g[slot], w, &mut src);
+            let (depth, field, src, tiles) = (&self.h, &field, &src, &self.tiles);
+            // a에서 b로 옮겨 가는 양 (옮기지 않으면 None)
+            let give = |a: usize, b: usize| {
+                if depth[a] < 0.01 || src[a] <= 0.0 {
+                    return None;
+                }
+                if depth[b] <= WET_THRESHOLD || field[b] <= field[a] {
+                    return None;
                 }
-                let neighbors = [idx - w, idx + w, idx - 1, idx + 1];
-                for &ni in &neighbors {
-                    if self.h[ni] < self.h[idx] {
-                        let diff = (self.h[idx] - self.h[ni]) * 0.02;
-                        let cap_rate = diff * (1.0 - self.paper_h[ni] * 0.5);
-                        if cap_rate > 0.0001 {
-                            self.h[ni] += cap_rate;
-                            self.h[idx] -= cap_rate;
-                            let ratio = (cap_rate * 0.3) / (self.h[idx] + 0.001);
-                            self.gr[ni] += self.gr[idx] * ratio;
-                            self.gg[ni] += self.gg[idx] * ratio;
-                            self.gb[ni] += self.gb[idx] * ratio;
-                            self.mask[ni] = (self.mask[ni] + 0.1).min(1.0);
+                Some(src[a] * ((field[b] - field[a]) * speed).min(0.1))
+            };
+            // 4칸 묶음: a..a+4에서 b..b+4로 옮겨 가는 칸 마스크와 양
+            let give4 = |a: usize, b: usize| {
+                let (fa, fb) = (F32x4::load(&field[a..]), F32x4::load(&field[b..]));
+                let sa = F32x4::load(&src[a..]);
+                let moves = splat(0.01)
+                    .le(F32x4::load(&depth[a..]))
+                    .and(splat(0.0).lt(sa))
+                    .and(splat(WET_THRESHOLD).lt(F32x4::load(&depth[b..])))
+                    .and(fa.lt(fb));
+                (moves, sa.mul(fb.sub(fa).mul(splat(speed)).min(splat(0.1))))
+            };
+            rows::for_each_row(&mut self.g[slot], w, |i, g| {
+                let inner = i > 0 && i + 1 < h;
+                for &(x0, x1) in tiles.spans(i) {
+                    let mut j = x0;
+                    while j < x1 {
+                        let k = i * w + j;
+                        if !inner || j == 0 || j + LANES >= w || j + LANES > x1 {
+                            // 가장자리 칸: 캔버스 밖 이웃은 없다
+                            let up = (i > 0).then(|| k - w);
+                            let left = (j > 0).then(|| k - 1);
+                            let right = (j + 1 < w).then(|| k + 1);
+                            let down = (i + 1 < h).then(|| k + w);
+                            let mut value = g[j];
+                            for l in [up, left].into_iter().flatten() {
+                                if let Some(amount) = give(l, k) {
+                                    value += amount;
+                                }
+                            }
+                            for l in [left, right, up, down].into_iter().flatten() {
+                                if let Some(amount) = give(k, l) {
+                                    value -= amount;
+                                }
+                            }
+                            for l in [right, down].into_iter().flatten() {
+                                if let Some(amount) = give(l, k) {
+                                    value += amount;
+                                }
+                            }
+                            g[j] = value;
+                            j += 1;
+                            continue;
+                        }
+                        let mut value = F32x4::load(&g[j..]);
+                        for l in [k - w, k - 1] {
+                            let (moves, amount) = give4(l, k);
+                            value = moves.select(value.add(amount), value);
+                        }
+                        for l in [k - 1, k + 1, k - w, k + w] {
+                            let (moves, amount) = give4(k, l);
+                            value = moves.select(value.sub(amount), value);
                         }
+                        for l in [k + 1, k + w] {
+                            let (moves, amount) = give4(l, k);
+                            value = moves.select(value.add(amount), value);
+                        }
+                        value.store(&mut g[j..]);
+                        j += LANES;
                     }
                 }
-            }
+            });
         }
+        self.scratch.extend([field, src]);
     }
 
-    fn back_run(&mut self) {
+    // 디버그 뷰: 칠해진 안료량으로 가중한 과립화(골짜기) 또는 응집(덩어리) 편향 (-1..1)
+    fn debug_contribution(&self, idx: usize) -> f32 {
+        let x = idx % self.width;
+        let y = idx / self.width;
+        let mut sum = 0.0;
+        let mut load = 0.0;
+        for (slot, &id) in self.palette.iter().enumerate() {
+            let Some(pig) = self.pigments.get(id) else {
+                continue;
+            };
+            let amount = self.d[slot][idx] + self.g[slot][idx];
+            let bias = if self.debug_view == DEBUG_VIEW_GRANULATION {
+                self.granulation_weight(pig) * (0.5 - self.paper_h[idx]) * 2.0
+            } else {
+                pig.flocculation * (floc_noise(x, y, id, FLOC_SCALE * self.scale) - 0.5) * 2.0
+            };
+            sum += amount * bias;
+            load += amount;
+        }
+        if load <= 1e-4 {
+            return 0.0;
+        }
+        (sum / load * load.min(1.0)).clamp(-1.0, 1.0)
+    }
+
+    // 번짐(백런/블룸)
+    // 반쯤 마른 영역으로 새 물이 들어오면 포화도 차이가 물을 섬유 쪽으로 끌어당긴다.
+    // 물과 함께 밀려난 부유 안료는 더 마른 전선에 쌓여 어두운 테두리를 남기고,
+    // 종이 섬유 높이와 노이즈가 전선의 진행을 막거나 허용해 가장자리가 프랙탈 모양이 된다.
+    fn bloom(&mut self) {
+        if self.bloom_strength <= 0.0 {
+            return;
+        }
         let w = self.width;
-        for i in 1..(self.height - 1) {
-            for j in 1..(w - 1) {
-                let idx = i * w + j;
-                if self.h[idx] < 0.005 || self.h[idx] > 0.05 {
+        let h = self.height;
+        let mut src_h = self.scratch.pop().unwrap_or_default();
+        let mut src_s = self.scratch.pop().unwrap_or_default();
+        self.tiles.copy(&self.h, w, &mut src_h);
+        self.tiles.copy(&self.s, w, &mut src_s);
+        let src_g: Vec<Vec<f32>> = self
+            .g
+            .iter()
+            .map(|g| {
+                let mut src = self.scratch.pop().unwrap_or_default();
+                self.tiles.copy(g, w, &mut src);
+                src
+            })
+            .collect();
+        let speed = self.step_dt() * self.grid_gain();
+
+        for i in 0..h {
+            for j in self.tiles.cols(i) {
+                let k = i * w + j;
+                if src_h[k] < BLOOM_FRESH_WATER {
                     continue;
                 }
-                let neighbors = [idx - w, idx + w, idx - 1, idx + 1];
-                let mut max_h = self.h[idx];
-                let mut max_ni = idx;
-                for &ni in &neighbors {
-                    if self.h[ni] > max_h {
-                        max_h = self.h[ni];
-                        max_ni = ni;
+                let neighbors = [
+                    (j > 0).then(|| k - 1),
+                    (j + 1 < w).then(|| k + 1),
+                    (i > 0).then(|| k - w),
+                    (i + 1 < h).then(|| k + w),
+                ];
+                for l in neighbors.into_iter().flatten() {
+                    if src_h[l] > BLOOM_DRY_SURFACE || src_s[l] < BLOOM_DAMP_MIN {
+                        continue;
+                    }
+                    let drive = ((src_s[k] + src_h[k]).min(self.capacity[k]) - src_s[l]).max(0.0)
+                        / CAPACITY_MAX;
+                    if drive <= 0.0 {
+                        continue;
+                    }
+                    let lx = (l % w) as u32;
+                    let ly = (l / w) as u32;
+                    let gate = self.paper_h[l] * 0.7 + cell_noise(lx, ly) * 0.3;
+                    if gate > 0.35 + 0.5 * drive {
+                        continue;
+                    }
+                    let fiber = 0.5 + (1.0 - self.paper_h[l]);
+                    let frac = (2.0 * self.bloom_strength * drive * fiber * speed).min(0.25);
+                    let dh = src_h[k] * frac;
+                    self.h[k] -= dh;
+                    self.h[l] += dh;
+                    // 반쯤 가라앉은 안료도 다시 쓸려 나가고, 마른 섬유일수록 밀려온 안료를 바로 붙잡는다
+                    let catch = (1.0 - src_s[l] / self.capacity[l]).clamp(0.0, 1.0) * 0.6;
+                    for ((g, d), src) in self.g.iter_mut().zip(self.d.iter_mut()).zip(&src_g) {
+                        let dg = src[k] * frac;
+                        let lifted = d[k] * frac * BLOOM_LIFT;
+                        g[k] -= dg;
+                        d[k] -= lifted;
+                        let moved = dg + lifted;
+                        g[l] += moved * (1.0 - catch);
+                        d[l] += moved * catch;
                     }
                 }
-                if max_ni != idx {
-                    let push = 0.005;
-                    self.dr[max_ni] += self.gr[idx] * push;
-                    self.dg[max_ni] += self.gg[idx] * push;
-                    self.db[max_ni] += self.gb[idx] * push;
+            }
+        }
+        self.scratch.extend(src_g);
+        self.scratch.extend([src_h, src_s]);
+    }
+
+    // 모세관층 (Curtis et al. 1997, CapillaryFlow)
+    // 표면 물이 섬유로 흡수되고, 섬유 속 물은 포화도가 낮은 이웃으로 번지며 천천히 증발한다.
+    fn capillary_flow(&mut self) {
+        let w = self.width;
+        let h = self.height;
+        let absorb = self.absorption * self.step_dt();
+        let splat = F32x4::splat;
+        let (capacity, paper_h, tiles) = (&self.capacity, &self.paper_h, &self.tiles);
+        rows::for_each_row2(&mut self.h, w, &mut self.s, w, |y, hh, ss| {
+            for &(x0, x1) in tiles.spans(y) {
+                for x in (x0..x1).step_by(LANES) {
+                    let n = LANES.min(x1 - x);
+                    let water = F32x4::load_partial(&hh[x..x + n]);
+                    let fiber = F32x4::load_partial(&ss[x..x + n]);
+                    let cap = F32x4::load_partial(&capacity[y * w + x..y * w + x + n]);
+                    let amount = splat(absorb)
+                        .min(cap.sub(fiber))
+                        .min(water)
+                        .max(splat(0.0));
+                    let wet = splat(0.0).lt(water);
+                    wet.select(water.sub(amount), water)
+                        .store_partial(&mut hh[x..x + n]);
+                    wet.select(fiber.add(amount), fiber)
+                        .store_partial(&mut ss[x..x + n]);
                 }
             }
+        });
+
+        // 번짐은 이전 포화도(src)만 보고 정해지므로, 셀마다 드나드는 양을 모아 자기 행만 쓴다.
+        // 더하는 순서를 셀을 차례로 돌며 이웃에 나눠 주던 순서와 맞춰 결과가 같다.
+        let mut src = self.scratch.pop().unwrap_or_default();
+        self.tiles.copy(&self.s, w, &mut src);
+        let rate = 0.25 * self.capillary_diffusion * self.step_dt() * self.grid_gain();
+        // k에서 낮은 이웃 l로 옮겨 갈 양
+        let flow = |k: usize, l: usize| {
+            if src[k] < DIFFUSE_MIN || src[l] >= src[k] {
+                return None;
+            }
+            // 섬유가 얇은 골짜기일수록 잘 번져 가장자리가 불규칙해진다
+            let fiber = 0.5 + (1.0 - paper_h[l]);
+            Some(((src[k] - src[l]).min(capacity[l] - src[l]) * (rate * fiber).min(0.2)).max(0.0))
+        };
+        // 4칸 묶음: k..k+4에서 l..l+4로 옮겨 가는 칸 마스크와 양
+        let flow4 = |k: usize, l: usize| {
+            let (sk, sl) = (F32x4::load(&src[k..]), F32x4::load(&src[l..]));
+            let moves = splat(DIFFUSE_MIN).le(sk).and(sl.lt(sk));
+            let fiber = splat(0.5).add(splat(1.0).sub(F32x4::load(&paper_h[l..])));
+            let room = F32x4::load(&capacity[l..]).sub(sl);
+            let amount = sk
+                .sub(sl)
+                .min(room)
+                .mul(splat(rate).mul(fiber).min(splat(0.2)))
+                .max(splat(0.0));
+            (moves, amount)
+        };
+        let dry = self.evaporation * 0.25 / self.scale;
+        rows::for_each_row(&mut self.s, w, |i, row| {
+            let inner = i > 0 && i + 1 < h;
+            for &(x0, x1) in tiles.spans(i) {
+                let mut j = x0;
+                while j < x1 {
+                    let c = i * w + j;
+                    // 안쪽 칸은 4칸씩
+                    if inner && j > 0 && j + LANES < w && j + LANES <= x1 {
+                        let mut x = F32x4::load(&src[c..]);
+                        let gain = |x: F32x4, k: usize| {
+                            let (moves, amount) = flow4(k, c);
+                            moves.select(x.add(amount), x)
+                        };
+                        let loss = |x: F32x4, l: usize| {
+                            let (moves, amount) = flow4(c, l);
+                            moves.select(x.sub(amount), x)
+                        };
+                        x = gain(x, c - w);
+                        x = gain(x, c - 1);
+                        x = loss(x, c - 1);
+                        x = loss(x, c + 1);
+                        x = loss(x, c - w);
+                        x = loss(x, c + w);
+                        x = gain(x, c + 1);
+                        x = gain(x, c + w);
+                        x.sub(splat(dry)).max(splat(0.0)).store(&mut row[j..]);
+                        j += LANES;
+                        continue;
+                    }
+                    let gain = |x: f32, k: usize| flow(k, c).map_or(x, |a| x + a);
+                    let loss = |x: f32, l: usize| flow(c, l).map_or(x, |a| x - a);
+                    let mut x = src[c];
+                    if i > 0 {
+                        x = gain(x, c - w);
+                    }
+                    if j > 0 {
+                        x = gain(x, c - 1);
+                        x = loss(x, c - 1);
+                    }
+                    if j + 1 < w {
+                        x = loss(x, c + 1);
+                    }
+                    if i > 0 {
+                        x = loss(x, c - w);
+                    }
+                    if i + 1 < h {
+                        x = loss(x, c + w);
+                    }
+                    if j + 1 < w {
+                        x = gain(x, c + 1);
+                    }
+                    if i + 1 < h {
+                        x = gain(x, c + w);
+                    }
+                    // 섬유 속 물의 증발
+                    row[j] = (x - dry).max(0.0);
+                    j += 1;
+                }
+            }
+        });
+        self.scratch.push(src);
+    }
+
+    fn rebuild_capacity_map(&mut self) {
+        for i in 0..self.total {
+            self.capacity[i] =
+                self.paper_h[i] * (CAPACITY_MAX - CAPACITY_MIN) + CAPACITY_MIN;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 되돌리기가 다루는 필드를 비트로 모은다
+    fn fields(e: &WatercolorEngine) -> Vec<Vec<u32>> {
+        let bits = |f: &Vec<f32>| f.iter().map(|x| x.to_bits()).collect();
+        let mut out: Vec<Vec<u32>> = [&e.h, &e.u, &e.v, &e.p, &e.mask, &e.s]
+            .into_iter()
+            .map(bits)
+            .collect();
+        out.extend(e.g.iter().chain(&e.d).map(bits));
+        out.push(e.palette.clone());
+        out
+    }
+
+    // 두 안료, 물 붓, 곡선 획을 섞고 중간중간 step을 돌린다
+    fn paint(e: &mut WatercolorEngine) {
+        let blue = e.find_pigment("ultramarine") as u32;
+        let red = e.find_pigment("alizarin") as u32;
+        e.apply_brush_stroke(4, 6, 40, 12, 4.0, 0.9, 0.8, blue, 0.0);
+        for _ in 0..4 {
+            e.step();
+        }
+        e.apply_water_brush_stroke(8, 30, 36, 30, 5.0, 1.0, 1.0, 0.0);
+        let samples = [6.0, 40.0, 0.5, 0.0, 20.0, 34.0, 0.9, 40.0, 38.0, 42.0, 0.7, 80.0];
+        e.apply_spline_stroke(&samples, 3.0, 0.8, 0.9, red);
+        for _ in 0..4 {
+            e.step();
+        }
+    }
+
+    // 서로 떨어진 세 획을 긋고 각 획 직전 상태를 돌려준다
+    fn three_strokes(e: &mut WatercolorEngine) -> Vec<Vec<Vec<u32>>> {
+        let blue = e.find_pigment("ultramarine") as u32;
+        let red = e.find_pigment("alizarin") as u32;
+        let mut checkpoints = Vec::new();
+        for (k, (x, pigment)) in [(10, blue), (80, red), (40, blue)].into_iter().enumerate() {
+            checkpoints.push(fields(e));
+            e.begin_stroke();
+            e.apply_brush_stroke(x, 10 + 20 * k as i32, x + 20, 30, 4.0, 0.9, 0.8, pigment, 0.0);
+            e.end_stroke();
+            for _ in 0..5 {
+                e.step();
+            }
+        }
+        checkpoints
+    }
+
+    #[test]
+    fn undo_redo_restore_checkpoints() {
+        let mut e = WatercolorEngine::new(112, 80);
+        let checkpoints = three_strokes(&mut e);
+        let last = fields(&e);
+        for k in (0..3).rev() {
+            assert!(e.undo());
+            assert!(fields(&e) == checkpoints[k], "{}번째 획 직전", k);
+        }
+        assert!(!e.undo());
+        // 되돌린 뒤의 step은 다시하기가 버린다
+        e.step();
+        for (k, checkpoint) in checkpoints.iter().enumerate().skip(1) {
+            assert!(e.redo());
+            assert!(fields(&e) == *checkpoint, "{}번째 획 직전", k);
+        }
+        assert!(e.redo());
+        assert!(fields(&e) == last);
+        assert!(!e.redo());
+        // 다시 되돌려도 같은 체크포인트로 간다
+        assert!(e.undo());
+        assert!(fields(&e) == checkpoints[2]);
+    }
+
+    #[test]
+    fn replay_renders_same_picture() {
+        let mut e = WatercolorEngine::new(48, 48);
+        e.start_journal();
+        paint(&mut e);
+        let mut r = WatercolorEngine::new(8, 8);
+        r.replay(&e.export_journal()).unwrap();
+        assert_eq!(r.render(), e.render());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_rows_match_serial() {
+        let run = |serial: bool| {
+            rows::SERIAL.with(|s| s.set(serial));
+            let mut e = WatercolorEngine::new(48, 48);
+            paint(&mut e);
+            let out = (e.render(), e.save_state());
+            rows::SERIAL.with(|s| s.set(false));
+            out
+        };
+        assert!(run(true) == run(false));
+    }
+
+    // SIMD와 scalar 구현은 한 빌드에 같이 들어가지 않으므로 결과의 해시를 고정해 두고
+    // 기본 빌드와 --features scalar 빌드가 모두 같은 값을 내는지 본다.
+    // 계산 결과가 일부러 바뀌는 변경이면 두 빌드에서 같은 새 값이 나오는지 확인하고 고친다.
+    #[test]
+    fn simd_matches_scalar_digest() {
+        let mut e = WatercolorEngine::new(48, 48);
+        paint(&mut e);
+        for _ in 0..20 {
+            e.step();
+        }
+        let pixels = e.render();
+        let words = fields(&e).into_iter().flatten();
+        let bytes = words.flat_map(u32::to_le_bytes).chain(pixels);
+        // FNV-1a 64
+        let digest = bytes.fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        assert_eq!(digest, 0x5002_1b24_086c_a36d, "{:#018x}", digest);
+    }
+
+    #[test]
+    fn dried_dab_sleeps_and_matches_untiled_run() {
+        let dab = || {
+            let mut e = WatercolorEngine::new(112, 80);
+            // 빨리 마르도록 증발만 키운다
+            e.set_physics(0.15, 0.02, 0.05, 5.0, 10);
+            let blue = e.find_pigment("ultramarine") as u32;
+            e.apply_brush_stroke(40, 30, 60, 40, 5.0, 0.9, 0.8, blue, 0.0);
+            e
+        };
+        let mut tiled = dab();
+        let mut steps = 0;
+        while tiled.active_area() > 0.0 {
+            assert!(steps < 1000, "{} step 뒤에도 깨어 있는 타일이 있다", steps);
+            tiled.step();
+            steps += 1;
+        }
+        // 매 step 모든 타일을 깨우면 캔버스 전체를 돈다
+        let mut full = dab();
+        for _ in 0..steps {
+            full.tiles.wake_all();
+            full.step();
+        }
+        assert!(fields(&tiled) == fields(&full));
+        assert!(tiled.render() == full.render());
+    }
+
+    #[test]
+    fn loaded_state_steps_like_original() {
+        let mut e = WatercolorEngine::new(48, 48);
+        paint(&mut e);
+        let mut l = WatercolorEngine::new(8, 8);
+        l.load_state(&e.save_state()).unwrap();
+        for _ in 0..6 {
+            e.step();
+            l.step();
+        }
+        assert_eq!(l.render(), e.render());
+    }
+
+    #[test]
+    fn undo_all_returns_to_blank() {
+        let blank = WatercolorEngine::new(48, 48).render();
+        let mut e = WatercolorEngine::new(48, 48);
+        paint(&mut e);
+        assert_ne!(e.render(), blank);
+        while e.undo() {}
+        assert_eq!(e.render(), blank);
+    }
+
+    #[test]
+    fn history_drops_oldest_over_budget() {
+        let mut e = WatercolorEngine::new(112, 80);
+        // 한 획의 차이만 겨우 들어가는 예산
+        e.history = History::new(112, 80, 200 << 10);
+        let checkpoints = three_strokes(&mut e);
+        let mut undone = 0;
+        while e.undo() {
+            undone += 1;
+        }
+        assert!((1..3).contains(&undone), "{}번 되돌림", undone);
+        assert!(fields(&e) == checkpoints[3 - undone]);
+    }
+
+    #[test]
+    fn full_palette_reuses_slot_left_with_residue() {
+        let mut e = WatercolorEngine::new(48, 32);
+        for id in 0..MAX_PIGMENTS as u32 {
+            e.apply_brush(8 + 4 * id as i32, 16, 3.0, 0.6, 0.8, id, 0.0, 1.0);
         }
+        let extra = MAX_PIGMENTS as u32;
+        assert!(e.palette_full());
+        assert_eq!(e.pigment_slot(extra), None);
+
+        // 흐름과 증발이 남긴 찌꺼기만 있는 슬롯
+        e.g[2].fill(0.0);
+        e.d[2].fill(0.0);
+        e.g[2][16 * 48 + 16] = 1e-6;
+        e.step();
+        assert!(!e.palette_full());
+        assert_eq!(e.pigment_slot(extra), Some(2));
+        assert_eq!(e.palette[2], extra);
+        assert!(e.g[2].iter().chain(&e.d[2]).all(|&v| v == 0.0));
+        assert!(e.palette_full());
     }
 }
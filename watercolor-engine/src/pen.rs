@@ -0,0 +1,69 @@
+// 펜 타블렛 입력을 붓 자국 모양으로 바꾼다.
+// 압력은 0..1, 기울기(tilt_x, tilt_y)와 붓대 회전(twist)은 PointerEvent와 같은 도 단위다.
+// 압력은 붓 크기와 물/안료 양을, 기울기는 붓 자국이 눕는 정도(aspect)와 방향을 정한다.
+
+// 압력이 0이어도 붓 크기의 이만큼은 닿는다
+const MIN_SIZE: f32 = 0.3;
+// 펜을 거의 눕혀도 붓 자국이 이보다 납작해지지는 않는다
+const MIN_ASPECT: f32 = 0.25;
+// 이보다 덜 기울면 세운 것으로 보고 기울기 방향 대신 획 방향을 쓴다
+const UPRIGHT: f32 = 5.0;
+// tan이 발산하지 않도록 축별 기울기를 여기서 자른다
+const MAX_TILT: f32 = 89.0;
+
+#[derive(Clone, Copy)]
+pub(crate) struct PenSample {
+    pub pressure: f32,
+    pub tilt_x: f32,
+    pub tilt_y: f32,
+    pub twist: f32,
+}
+
+// apply_brush 한 번에 넘길 값. radius는 긴 축 반지름, load는 물/안료에 곱한다.
+pub(crate) struct Footprint {
+    pub radius: f32,
+    pub aspect: f32,
+    pub angle: f32,
+    pub load: f32,
+}
+
+// 압력에 따른 붓 반지름 (압력 1이면 size)
+pub(crate) fn pressure_size(size: f32, pressure: f32) -> f32 {
+    size * (MIN_SIZE + (1.0 - MIN_SIZE) * pressure.clamp(0.0, 1.0))
+}
+
+impl PenSample {
+    // heading: 펜을 세웠을 때 붓 자국의 긴 축 방향 (라디안)
+    pub(crate) fn footprint(&self, size: f32, heading: f32) -> Footprint {
+        let pressure = self.pressure.clamp(0.0, 1.0);
+        let tx = self.tilt_x.clamp(-MAX_TILT, MAX_TILT).to_radians().tan();
+        let ty = self.tilt_y.clamp(-MAX_TILT, MAX_TILT).to_radians().tan();
+        // 펜 축과 종이 법선 사이 각
+        let tilt = (tx * tx + ty * ty).sqrt().atan();
+        let base = if tilt.to_degrees() > UPRIGHT {
+            ty.atan2(tx)
+        } else {
+            heading
+        };
+        // 누운 붓은 기울어진 쪽으로 길어지고 옆으로 좁아진다 (닿는 넓이는 그대로)
+        let aspect = tilt.cos().max(MIN_ASPECT);
+        let radius = pressure_size(size, pressure);
+        Footprint {
+            radius: radius / aspect.sqrt(),
+            aspect,
+            angle: base + self.twist.to_radians(),
+            load: pressure,
+        }
+    }
+
+    // 두 표본 사이 보간. 회전은 짧은 쪽으로 돈다.
+    pub(crate) fn lerp(&self, other: &PenSample, t: f32) -> PenSample {
+        let turn = (other.twist - self.twist + 540.0).rem_euclid(360.0) - 180.0;
+        PenSample {
+            pressure: self.pressure + (other.pressure - self.pressure) * t,
+            tilt_x: self.tilt_x + (other.tilt_x - self.tilt_x) * t,
+            tilt_y: self.tilt_y + (other.tilt_y - self.tilt_y) * t,
+            twist: self.twist + turn * t,
+        }
+    }
+}
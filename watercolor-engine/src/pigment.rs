@@ -0,0 +1,137 @@
+// 안료 라이브러리
+// 각 안료는 KM 흡수/산란 계수와 물리적 성질(밀도, 착색력, 과립화, 응집)을 가진다.
+// K/S 값은 Curtis et al. 1997의 실측 표를 기반으로 엔진 농도 단위에 맞게 조정했다.
+
+use crate::km::KsCoeffs;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pigment {
+    pub name: String,
+    pub ks: KsCoeffs,
+    // 침전 속도 (무거운 안료일수록 빨리 종이에 가라앉는다)
+    pub density: f32,
+    // 착색력: 종이 섬유에 물들어 다시 들어올리기 어려운 정도 (0..1)
+    pub staining: f32,
+    // 과립화: 종이 골짜기에 모이는 정도 (0..1)
+    pub granulation: f32,
+    // 응집: 안료 입자끼리 뭉치는 정도 (0..1)
+    pub flocculation: f32,
+}
+
+impl Pigment {
+    pub fn new(
+        name: &str,
+        k: [f32; 3],
+        s: [f32; 3],
+        density: f32,
+        staining: f32,
+        granulation: f32,
+        flocculation: f32,
+    ) -> Pigment {
+        Pigment {
+            name: name.to_string(),
+            ks: KsCoeffs { k, s },
+            density: density.max(0.0),
+            staining: staining.clamp(0.0, 1.0),
+            granulation: granulation.clamp(0.0, 1.0),
+            flocculation: flocculation.clamp(0.0, 1.0),
+        }
+    }
+
+    // 얇게 칠했을 때 주어진 색으로 보이는 투명 안료를 만든다.
+    // 흰 종이 위 박막의 반사율이 대략 exp(-2K)이므로 K = -ln(R) / 2 로 역산한다.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_rgb(
+        name: &str,
+        r: f32,
+        g: f32,
+        b: f32,
+        density: f32,
+        staining: f32,
+        granulation: f32,
+        flocculation: f32,
+    ) -> Pigment {
+        let mut k = [0.0f32; 3];
+        let mut s = [0.0f32; 3];
+        for (c, &v) in [r, g, b].iter().enumerate() {
+            let refl = v.clamp(0.03, 0.98);
+            k[c] = -refl.ln() * 0.5;
+            s[c] = 0.02 + 0.06 * refl;
+        }
+        Pigment::new(name, k, s, density, staining, granulation, flocculation)
+    }
+
+    // 두꺼운 층(마스톤)의 반사율 R∞ = 1 + K/S - sqrt((K/S)^2 + 2K/S)
+    pub fn masstone(&self) -> [f32; 3] {
+        let mut out = [0.0f32; 3];
+        for (c, o) in out.iter_mut().enumerate() {
+            let ratio = self.ks.k[c] / self.ks.s[c].max(1e-4);
+            *o = (1.0 + ratio - (ratio * ratio + 2.0 * ratio).sqrt()).clamp(0.0, 1.0);
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PigmentLibrary {
+    pigments: Vec<Pigment>,
+}
+
+impl PigmentLibrary {
+    #[rustfmt::skip]
+    pub fn builtin() -> PigmentLibrary {
+        let pigments = vec![
+            Pigment::new("ultramarine", [0.86, 0.55, 0.06], [0.005, 0.005, 0.09], 0.07, 0.2, 0.9, 0.6),
+            Pigment::new("cerulean", [1.52, 0.32, 0.25], [0.06, 0.26, 0.40], 0.08, 0.15, 0.8, 0.2),
+            Pigment::new("viridian", [1.2, 0.35, 0.9], [0.02, 0.08, 0.03], 0.05, 0.5, 0.3, 0.1),
+            Pigment::new("alizarin", [0.22, 1.47, 0.57], [0.05, 0.003, 0.03], 0.03, 0.8, 0.05, 0.0),
+            Pigment::new("vermilion", [0.14, 1.08, 1.68], [0.77, 0.015, 0.018], 0.09, 0.3, 0.3, 0.1),
+            Pigment::new("gamboge", [0.06, 0.21, 1.78], [0.50, 0.88, 0.009], 0.04, 0.4, 0.05, 0.0),
+            Pigment::new("burnt_sienna", [0.35, 0.9, 1.4], [0.45, 0.2, 0.1], 0.08, 0.3, 0.6, 0.5),
+            Pigment::new("paynes_grey", [1.1, 1.0, 0.8], [0.03, 0.03, 0.05], 0.05, 0.5, 0.2, 0.3),
+            Pigment::new("indigo", [1.3, 1.2, 0.5], [0.01, 0.01, 0.04], 0.04, 0.7, 0.1, 0.2),
+            Pigment::new("yellow_ochre", [0.15, 0.35, 1.2], [0.6, 0.45, 0.12], 0.1, 0.1, 0.7, 0.4),
+            Pigment::new("sap_green", [1.62, 0.61, 1.64], [0.01, 0.012, 0.003], 0.04, 0.6, 0.1, 0.1),
+            Pigment::new("rose_madder", [0.1, 0.9, 0.4], [0.08, 0.02, 0.05], 0.03, 0.5, 0.0, 0.0),
+            Pigment::new("phthalo_blue", [1.7, 0.6, 0.1], [0.004, 0.01, 0.06], 0.03, 0.95, 0.0, 0.0),
+        ];
+        PigmentLibrary { pigments }
+    }
+
+    pub fn from_pigments(pigments: Vec<Pigment>) -> PigmentLibrary {
+        PigmentLibrary { pigments }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Pigment> {
+        self.pigments.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pigments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pigments.is_empty()
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Pigment> {
+        self.pigments.get(id as usize)
+    }
+
+    pub fn find(&self, name: &str) -> Option<u32> {
+        self.pigments
+            .iter()
+            .position(|p| p.name == name)
+            .map(|i| i as u32)
+    }
+
+    // 같은 이름이 있으면 덮어쓰고, 없으면 새 id를 발급한다.
+    pub fn add(&mut self, pigment: Pigment) -> u32 {
+        if let Some(id) = self.find(&pigment.name) {
+            self.pigments[id as usize] = pigment;
+            return id;
+        }
+        self.pigments.push(pigment);
+        (self.pigments.len() - 1) as u32
+    }
+}
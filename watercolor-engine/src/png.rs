@@ -0,0 +1,347 @@
+// 의존성 없는 PNG/PPM 인코더 (8비트 RGBA)
+// 행마다 PNG 필터를 고르고 고정 허프만 deflate(LZ77)로 압축한다.
+// 정수 연산만 쓰므로 어느 플랫폼에서 만들어도 바이트가 같다.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+// RGBA 한 픽셀의 바이트 수 (필터가 왼쪽 픽셀을 볼 때 쓴다)
+const BPP: usize = 4;
+
+// LZ77 창 크기와 일치 길이 범위 (deflate 규격)
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// 3바이트 해시 테이블 크기 (2의 거듭제곱)
+const HASH_BITS: u32 = 15;
+// 한 위치에서 따라가 볼 이전 후보 수
+const MAX_CHAIN: usize = 64;
+
+// 길이 부호 257..285의 기본 길이와 추가 비트 수
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+// 거리 부호 0..29의 기본 거리와 추가 비트 수
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+pub(crate) fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut c = 0xffff_ffffu32;
+    for part in parts {
+        for &b in part.iter() {
+            c = CRC_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8);
+        }
+    }
+    c ^ 0xffff_ffff
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    // 5552바이트마다 나머지를 취해도 u32가 넘치지 않는다
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(tag);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&[tag, data]).to_be_bytes());
+}
+
+// deflate 비트 열 (낮은 비트부터 채운다)
+struct Bits {
+    out: Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl Bits {
+    fn put(&mut self, value: u32, n: u32) {
+        self.acc |= value << self.len;
+        self.len += n;
+        while self.len >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    // 허프만 부호는 높은 비트부터 쓴다
+    fn code(&mut self, code: u32, n: u32) {
+        self.put(code.reverse_bits() >> (32 - n), n);
+    }
+
+    // 고정 허프만 리터럴/길이 부호 (RFC 1951 3.2.6)
+    fn symbol(&mut self, sym: u32) {
+        match sym {
+            0..=143 => self.code(0x30 + sym, 8),
+            144..=255 => self.code(0x190 + sym - 144, 9),
+            256..=279 => self.code(sym - 256, 7),
+            _ => self.code(0xc0 + sym - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+fn hash3(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+// 고정 허프만 블록 하나로 압축한다. 해시 체인에서 가장 긴 (같으면 가장 가까운) 일치를 고른다.
+fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits {
+        out: Vec::with_capacity(data.len() / 4 + 16),
+        acc: 0,
+        len: 0,
+    };
+    // BFINAL = 1, BTYPE = 01 (고정 허프만)
+    bits.put(1, 1);
+    bits.put(1, 2);
+
+    let n = data.len();
+    // 해시별 가장 최근 위치와, 위치마다 같은 해시의 바로 이전 위치
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + MIN_MATCH <= n {
+            let h = hash3(data, i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+    let mut i = 0;
+    while i < n {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH <= n {
+            let limit = (n - i).min(MAX_MATCH);
+            let mut cand = head[hash3(data, i)];
+            let mut chain = 0;
+            while cand != usize::MAX && i - cand <= WINDOW && chain < MAX_CHAIN {
+                // 지금까지의 최장 길이 끝 바이트가 다르면 더 길어질 수 없다
+                let len =
+                    if data[cand + best_len.min(limit - 1)] != data[i + best_len.min(limit - 1)] {
+                        0
+                    } else {
+                        data[cand..cand + limit]
+                            .iter()
+                            .zip(&data[i..i + limit])
+                            .take_while(|(x, y)| x == y)
+                            .count()
+                    };
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - cand;
+                    if len == limit {
+                        break;
+                    }
+                }
+                let next = prev[cand % WINDOW];
+                // 창 밖으로 밀려나 다른 위치가 덮어쓴 칸이면 멈춘다
+                if next == usize::MAX || next >= cand {
+                    break;
+                }
+                cand = next;
+                chain += 1;
+            }
+        }
+        if best_len >= MIN_MATCH {
+            let lc = LENGTH_BASE.partition_point(|&b| b as usize <= best_len) - 1;
+            bits.symbol(257 + lc as u32);
+            bits.put(
+                (best_len - LENGTH_BASE[lc] as usize) as u32,
+                LENGTH_EXTRA[lc] as u32,
+            );
+            let dc = DIST_BASE.partition_point(|&b| b as usize <= best_dist) - 1;
+            bits.code(dc as u32, 5);
+            bits.put(
+                (best_dist - DIST_BASE[dc] as usize) as u32,
+                DIST_EXTRA[dc] as u32,
+            );
+            for k in i..i + best_len {
+                insert(k, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            bits.symbol(data[i] as u32);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    bits.symbol(256);
+    bits.finish()
+}
+
+fn zlib(raw: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend_from_slice(&deflate_fixed(raw));
+    out.extend_from_slice(&adler32(raw).to_be_bytes());
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// 한 행을 필터 종류 0..4 (없음, Sub, Up, Average, Paeth)로 거른다. prev는 윗 행 (첫 행이면 0).
+fn filter_row(kind: u8, row: &[u8], prev: &[u8], out: &mut Vec<u8>) {
+    out.push(kind);
+    for x in 0..row.len() {
+        let a = if x >= BPP { row[x - BPP] } else { 0 };
+        let b = prev[x];
+        let c = if x >= BPP { prev[x - BPP] } else { 0 };
+        let pred = match kind {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        out.push(row[x].wrapping_sub(pred));
+    }
+}
+
+// 행마다 부호 있는 바이트 합이 가장 작은 필터를 고른다 (libpng와 같은 어림)
+fn filter_rows(rgba: &[u8], stride: usize) -> Vec<u8> {
+    let zero = vec![0u8; stride];
+    let mut raw = Vec::with_capacity((stride + 1) * (rgba.len() / stride.max(1)));
+    let mut trial = Vec::with_capacity(stride + 1);
+    let mut prev: &[u8] = &zero;
+    for row in rgba.chunks(stride.max(1)) {
+        let mut best = (u64::MAX, 0);
+        for kind in 0..5 {
+            trial.clear();
+            filter_row(kind, row, prev, &mut trial);
+            let cost = trial[1..]
+                .iter()
+                .map(|&v| (v as i8).unsigned_abs() as u64)
+                .sum::<u64>();
+            if cost < best.0 {
+                best = (cost, kind);
+            }
+        }
+        filter_row(best.1, row, prev, &mut raw);
+        prev = row;
+    }
+    raw
+}
+
+// render() 출력(RGBA, 행 우선)을 PNG 파일 바이트로 만든다.
+pub fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    encode_rgba_with_text(width, height, rgba, &[])
+}
+
+// (키워드, 값) 쌍을 tEXt 청크로 IHDR 바로 뒤에 넣는다.
+// 키워드는 1~79바이트 Latin-1이어야 하므로 ASCII만 남기고 잘라낸다.
+pub fn encode_rgba_with_text(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    text: &[(&str, &str)],
+) -> Vec<u8> {
+    let stride = width as usize * 4;
+    assert_eq!(
+        rgba.len(),
+        stride * height as usize,
+        "RGBA 버퍼 크기가 맞지 않습니다"
+    );
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 비트 깊이 8, 색 형식 6(RGBA), 압축 0, 필터 0, 인터레이스 없음
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    // 각 행 앞에 필터 종류 바이트가 붙는다
+    let raw = filter_rows(rgba, stride);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    for (key, value) in text {
+        let key: Vec<u8> = key
+            .bytes()
+            .filter(|b| (0x20..0x7f).contains(b))
+            .take(79)
+            .collect();
+        if key.is_empty() {
+            continue;
+        }
+        let mut body = key;
+        body.push(0);
+        body.extend(value.bytes().filter(|b| b.is_ascii() && *b != 0));
+        write_chunk(&mut out, b"tEXt", &body);
+    }
+    write_chunk(&mut out, b"IDAT", &zlib(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+// 알파를 버리고 바이너리 PPM(P6)으로 만든다.
+pub fn encode_ppm(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let pixels = width as usize * height as usize;
+    assert_eq!(rgba.len(), pixels * 4, "RGBA 버퍼 크기가 맞지 않습니다");
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    out.reserve(pixels * 3);
+    for px in rgba.chunks_exact(4) {
+        out.extend_from_slice(&px[..3]);
+    }
+    out
+}
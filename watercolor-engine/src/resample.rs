@@ -0,0 +1,128 @@
+// 캔버스를 정수 배율로 키운다 (고해상도 재렌더링, replay_scaled)
+// 수위/농도/포화도는 셀 크기와 무관한 값이라 그대로 보간하고,
+// 셀 단위로 잰 속도와 압력만 배율에 맞게 환산한다.
+
+use crate::history::History;
+use crate::journal::Journal;
+use crate::tiles::Tiles;
+use crate::{WatercolorEngine, HISTORY_BYTES};
+
+// 셀 중심 기준 쌍선형 보간 (가장자리는 바깥 셀을 복제)
+fn bilinear(src: &[f32], w: usize, h: usize, k: usize) -> Vec<f32> {
+    let (fw, fh) = (w * k, h * k);
+    let mut out = vec![0.0; fw * fh];
+    let coord = |x: usize, n: usize| {
+        let c = ((x as f32 + 0.5) / k as f32 - 0.5).max(0.0);
+        let i0 = (c as usize).min(n - 1);
+        let i1 = (i0 + 1).min(n - 1);
+        (i0, i1, c - i0 as f32)
+    };
+    for y in 0..fh {
+        let (y0, y1, ty) = coord(y, h);
+        for x in 0..fw {
+            let (x0, x1, tx) = coord(x, w);
+            let top = src[y0 * w + x0] + (src[y0 * w + x1] - src[y0 * w + x0]) * tx;
+            let bottom = src[y1 * w + x0] + (src[y1 * w + x1] - src[y1 * w + x0]) * tx;
+            out[y * fw + x] = top + (bottom - top) * ty;
+        }
+    }
+    out
+}
+
+// x 방향 경계면 속도 ((w + 1) x h): 경계면 사이는 선형 보간, 행은 가장 가까운 원본 행
+fn faces_u(src: &[f32], w: usize, h: usize, k: usize, gain: f32) -> Vec<f32> {
+    let (fw, fh) = (w * k, h * k);
+    let mut out = vec![0.0; (fw + 1) * fh];
+    for y in 0..fh {
+        let row = &src[(y / k) * (w + 1)..(y / k + 1) * (w + 1)];
+        for f in 0..=fw {
+            let f0 = (f / k).min(w);
+            let f1 = (f0 + 1).min(w);
+            let t = (f % k) as f32 / k as f32;
+            out[y * (fw + 1) + f] = (row[f0] + (row[f1] - row[f0]) * t) * gain;
+        }
+    }
+    out
+}
+
+// y 방향 경계면 속도 (w x (h + 1))
+fn faces_v(src: &[f32], w: usize, h: usize, k: usize, gain: f32) -> Vec<f32> {
+    let (fw, fh) = (w * k, h * k);
+    let mut out = vec![0.0; fw * (fh + 1)];
+    for f in 0..=fh {
+        let f0 = (f / k).min(h);
+        let f1 = (f0 + 1).min(h);
+        let t = (f % k) as f32 / k as f32;
+        for x in 0..fw {
+            let a = src[f0 * w + x / k];
+            let b = src[f1 * w + x / k];
+            out[f * fw + x] = (a + (b - a) * t) * gain;
+        }
+    }
+    out
+}
+
+impl WatercolorEngine {
+    // k배 큰 캔버스로 현재 상태를 옮긴다. 되돌리기와 붓 기록은 비운다.
+    pub(crate) fn upscaled(&self, k: usize) -> WatercolorEngine {
+        let (w, h) = (self.width, self.height);
+        let (width, height) = (w * k, h * k);
+        let total = width * height;
+        let up = |src: &[f32]| bilinear(src, w, h, k);
+        let kf = k as f32;
+        let mut e = WatercolorEngine {
+            width,
+            height,
+            total,
+            h: up(&self.h),
+            // 속도는 셀/시간 단위라 배율만큼, 압력은 기울기가 속도가 되므로 배율²만큼 키운다
+            u: faces_u(&self.u, w, h, k, kf),
+            v: faces_v(&self.v, w, h, k, kf),
+            p: up(&self.p).into_iter().map(|p| p * kf * kf).collect(),
+            mask: up(&self.mask),
+            s: up(&self.s),
+            capacity: vec![0.0; total],
+            wet_mask: vec![0.0; total],
+            edge_dist: vec![0.0; total],
+            tiles: Tiles::new(width, height),
+            scratch: Vec::new(),
+            g: self.g.iter().map(|g| up(g)).collect(),
+            d: self.d.iter().map(|d| up(d)).collect(),
+            pigments: self.pigments.clone(),
+            palette: self.palette.clone(),
+            free_slots: None,
+            brush: self.brush.clone(),
+            paper_h: up(&self.paper_h),
+            paper_render: vec![0.5; total],
+            silhouette_map: up(&self.silhouette_map),
+            silhouette_edge: vec![0.0; total],
+            pixels: vec![255u8; total * 4],
+            dt: self.dt,
+            evaporation: self.evaporation,
+            viscosity: self.viscosity,
+            pressure: self.pressure,
+            iterations: self.iterations,
+            adhesion: self.adhesion,
+            granularity: self.granularity,
+            absorption: self.absorption,
+            capillary_diffusion: self.capillary_diffusion,
+            bloom_strength: self.bloom_strength,
+            edge_darkening: self.edge_darkening,
+            scale: self.scale * kf,
+            divergence_residual: self.divergence_residual,
+            history: History::new(width, height, HISTORY_BYTES),
+            stroke_open: false,
+            journal: Journal::default(),
+            show_texture: self.show_texture,
+            debug_view: self.debug_view,
+            silhouette_strength: self.silhouette_strength,
+            edge_bleed_strength: self.edge_bleed_strength,
+            drybrush: self.drybrush,
+            tip: self.tip.clone(),
+        };
+        e.rebuild_paper_render_map();
+        e.rebuild_silhouette_edge_map();
+        e.rebuild_capacity_map();
+        e
+    }
+}
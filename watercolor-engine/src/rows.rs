@@ -0,0 +1,58 @@
+// 행 단위 반복. parallel 기능이 켜지면 rayon으로 행을 나누어 돌린다.
+// 넘겨주는 계산은 자기 행만 쓰고 다른 행의 출력은 읽지 않아야 한다.
+// 그러면 행을 어떤 순서로 돌려도 같은 값이 나오므로 직렬/병렬 결과가 비트 단위로 같다.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[cfg(all(test, feature = "parallel"))]
+thread_local! {
+    // 켜면 이 스레드에서 부르는 행 반복은 직렬로 돈다 (병렬 결과와 비교하는 테스트용)
+    pub(crate) static SERIAL: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+#[cfg(feature = "parallel")]
+fn serial() -> bool {
+    #[cfg(test)]
+    return SERIAL.with(|s| s.get());
+    #[cfg(not(test))]
+    false
+}
+
+pub(crate) fn for_each_row<T, F>(data: &mut [T], len: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    if !serial() {
+        data.par_chunks_mut(len)
+            .enumerate()
+            .for_each(|(i, row)| f(i, row));
+        return;
+    }
+    data.chunks_mut(len)
+        .enumerate()
+        .for_each(|(i, row)| f(i, row));
+}
+
+// 두 버퍼의 같은 번호 행을 함께 넘긴다 (행 길이는 달라도 된다)
+pub(crate) fn for_each_row2<A, B, F>(a: &mut [A], a_len: usize, b: &mut [B], b_len: usize, f: F)
+where
+    A: Send,
+    B: Send,
+    F: Fn(usize, &mut [A], &mut [B]) + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    if !serial() {
+        a.par_chunks_mut(a_len)
+            .zip(b.par_chunks_mut(b_len))
+            .enumerate()
+            .for_each(|(i, (ra, rb))| f(i, ra, rb));
+        return;
+    }
+    a.chunks_mut(a_len)
+        .zip(b.chunks_mut(b_len))
+        .enumerate()
+        .for_each(|(i, (ra, rb))| f(i, ra, rb));
+}
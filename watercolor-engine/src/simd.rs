@@ -0,0 +1,493 @@
+// 4칸 묶음 f32 벡터 (render, deposition, move_fluid 안쪽 루프용)
+// 대상에 따라 SSE2(x86/x86_64), wasm simd128, 배열(스칼라) 구현 중 하나가 컴파일된다.
+// 어느 구현이든 같은 IEEE 연산을 같은 순서로 하고(FMA 없음) min/max도 "a < b ? a : b"로 맞추므로
+// 결과가 비트 단위로 같다. scalar 기능을 켜면 SIMD를 끄고 배열 구현을 쓴다 (비교/검증용).
+
+pub(crate) const LANES: usize = 4;
+
+#[cfg(all(
+    not(feature = "scalar"),
+    any(
+        target_arch = "x86_64",
+        all(target_arch = "x86", target_feature = "sse2")
+    )
+))]
+mod imp {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct F32x4(__m128);
+
+    // SSE2는 x86_64의 기본 기능이고 x86에서는 target_feature로 확인했으므로 호출이 안전하다
+    impl F32x4 {
+        #[inline(always)]
+        pub(crate) fn splat(x: f32) -> Self {
+            Self(unsafe { _mm_set1_ps(x) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn from_array(a: [f32; 4]) -> Self {
+            Self(unsafe { _mm_loadu_ps(a.as_ptr()) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn to_array(self) -> [f32; 4] {
+            let mut out = [0.0; 4];
+            unsafe { _mm_storeu_ps(out.as_mut_ptr(), self.0) };
+            out
+        }
+
+        #[inline(always)]
+        pub(crate) fn load(src: &[f32]) -> Self {
+            assert!(src.len() >= 4);
+            Self(unsafe { _mm_loadu_ps(src.as_ptr()) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn store(self, dst: &mut [f32]) {
+            assert!(dst.len() >= 4);
+            unsafe { _mm_storeu_ps(dst.as_mut_ptr(), self.0) };
+        }
+
+        #[inline(always)]
+        pub(crate) fn add(self, o: Self) -> Self {
+            Self(unsafe { _mm_add_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn sub(self, o: Self) -> Self {
+            Self(unsafe { _mm_sub_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn mul(self, o: Self) -> Self {
+            Self(unsafe { _mm_mul_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn div(self, o: Self) -> Self {
+            Self(unsafe { _mm_div_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn sqrt(self) -> Self {
+            Self(unsafe { _mm_sqrt_ps(self.0) })
+        }
+
+        // minps/maxps는 정확히 "a < b ? a : b", "a > b ? a : b"이다
+        #[inline(always)]
+        pub(crate) fn min(self, o: Self) -> Self {
+            Self(unsafe { _mm_min_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn max(self, o: Self) -> Self {
+            Self(unsafe { _mm_max_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn lt(self, o: Self) -> Self {
+            Self(unsafe { _mm_cmplt_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn le(self, o: Self) -> Self {
+            Self(unsafe { _mm_cmple_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn and(self, o: Self) -> Self {
+            Self(unsafe { _mm_and_ps(self.0, o.0) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn or(self, o: Self) -> Self {
+            Self(unsafe { _mm_or_ps(self.0, o.0) })
+        }
+
+        // self가 마스크일 때 참인 칸은 a, 거짓인 칸은 b (any는 참인 칸이 하나라도 있는지)
+        #[inline(always)]
+        pub(crate) fn select(self, a: Self, b: Self) -> Self {
+            Self(unsafe { _mm_or_ps(_mm_and_ps(self.0, a.0), _mm_andnot_ps(self.0, b.0)) })
+        }
+
+        #[inline(always)]
+        pub(crate) fn any(self) -> bool {
+            unsafe { _mm_movemask_ps(self.0) != 0 }
+        }
+
+        // m = t + 1.5 * 2^23 (t를 정수로 반올림한 값이 가수 아래 비트에 있다) -> 2^round(t)
+        #[inline(always)]
+        pub(crate) fn pow2_from_magic(self) -> Self {
+            Self(unsafe {
+                let bits =
+                    _mm_add_epi32(_mm_castps_si128(self.0), _mm_set1_epi32(super::POW2_BIAS));
+                _mm_castsi128_ps(_mm_slli_epi32::<23>(bits))
+            })
+        }
+    }
+}
+
+#[cfg(all(
+    not(feature = "scalar"),
+    target_arch = "wasm32",
+    target_feature = "simd128"
+))]
+mod imp {
+    use core::arch::wasm32::*;
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct F32x4(v128);
+
+    impl F32x4 {
+        #[inline(always)]
+        pub(crate) fn splat(x: f32) -> Self {
+            Self(f32x4_splat(x))
+        }
+
+        #[inline(always)]
+        pub(crate) fn from_array(a: [f32; 4]) -> Self {
+            Self(f32x4(a[0], a[1], a[2], a[3]))
+        }
+
+        #[inline(always)]
+        pub(crate) fn to_array(self) -> [f32; 4] {
+            [
+                f32x4_extract_lane::<0>(self.0),
+                f32x4_extract_lane::<1>(self.0),
+                f32x4_extract_lane::<2>(self.0),
+                f32x4_extract_lane::<3>(self.0),
+            ]
+        }
+
+        #[inline(always)]
+        pub(crate) fn load(src: &[f32]) -> Self {
+            Self::from_array([src[0], src[1], src[2], src[3]])
+        }
+
+        #[inline(always)]
+        pub(crate) fn store(self, dst: &mut [f32]) {
+            dst[..4].copy_from_slice(&self.to_array());
+        }
+
+        #[inline(always)]
+        pub(crate) fn add(self, o: Self) -> Self {
+            Self(f32x4_add(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn sub(self, o: Self) -> Self {
+            Self(f32x4_sub(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn mul(self, o: Self) -> Self {
+            Self(f32x4_mul(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn div(self, o: Self) -> Self {
+            Self(f32x4_div(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn sqrt(self) -> Self {
+            Self(f32x4_sqrt(self.0))
+        }
+
+        // pmin(x, y) = y < x ? y : x 이므로 인자를 뒤집어 "a < b ? a : b"를 만든다
+        #[inline(always)]
+        pub(crate) fn min(self, o: Self) -> Self {
+            Self(f32x4_pmin(o.0, self.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn max(self, o: Self) -> Self {
+            Self(f32x4_pmax(o.0, self.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn lt(self, o: Self) -> Self {
+            Self(f32x4_lt(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn le(self, o: Self) -> Self {
+            Self(f32x4_le(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn and(self, o: Self) -> Self {
+            Self(v128_and(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn or(self, o: Self) -> Self {
+            Self(v128_or(self.0, o.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn select(self, a: Self, b: Self) -> Self {
+            Self(v128_bitselect(a.0, b.0, self.0))
+        }
+
+        #[inline(always)]
+        pub(crate) fn any(self) -> bool {
+            v128_any_true(self.0)
+        }
+
+        #[inline(always)]
+        pub(crate) fn pow2_from_magic(self) -> Self {
+            let bits = i32x4_add(self.0, i32x4_splat(super::POW2_BIAS));
+            Self(i32x4_shl(bits, 23))
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(
+        not(feature = "scalar"),
+        any(
+            target_arch = "x86_64",
+            all(target_arch = "x86", target_feature = "sse2")
+        )
+    ),
+    all(
+        not(feature = "scalar"),
+        target_arch = "wasm32",
+        target_feature = "simd128"
+    )
+)))]
+mod imp {
+    // 스칼라 대체 구현: 칸마다 같은 연산을 한다. 마스크는 모든 비트가 1인 값이다.
+    #[derive(Clone, Copy)]
+    pub(crate) struct F32x4([f32; 4]);
+
+    const TRUE: f32 = f32::from_bits(u32::MAX);
+
+    impl F32x4 {
+        #[inline(always)]
+        fn map(self, f: impl Fn(f32) -> f32) -> Self {
+            Self(self.0.map(f))
+        }
+
+        #[inline(always)]
+        fn zip(self, o: Self, f: impl Fn(f32, f32) -> f32) -> Self {
+            let [a, b, c, d] = self.0;
+            let [e, g, h, i] = o.0;
+            Self([f(a, e), f(b, g), f(c, h), f(d, i)])
+        }
+
+        #[inline(always)]
+        fn mask(b: bool) -> f32 {
+            if b {
+                TRUE
+            } else {
+                0.0
+            }
+        }
+
+        #[inline(always)]
+        pub(crate) fn splat(x: f32) -> Self {
+            Self([x; 4])
+        }
+
+        #[inline(always)]
+        pub(crate) fn from_array(a: [f32; 4]) -> Self {
+            Self(a)
+        }
+
+        #[inline(always)]
+        pub(crate) fn to_array(self) -> [f32; 4] {
+            self.0
+        }
+
+        #[inline(always)]
+        pub(crate) fn load(src: &[f32]) -> Self {
+            Self([src[0], src[1], src[2], src[3]])
+        }
+
+        #[inline(always)]
+        pub(crate) fn store(self, dst: &mut [f32]) {
+            dst[..4].copy_from_slice(&self.0);
+        }
+
+        #[inline(always)]
+        pub(crate) fn add(self, o: Self) -> Self {
+            self.zip(o, |a, b| a + b)
+        }
+
+        #[inline(always)]
+        pub(crate) fn sub(self, o: Self) -> Self {
+            self.zip(o, |a, b| a - b)
+        }
+
+        #[inline(always)]
+        pub(crate) fn mul(self, o: Self) -> Self {
+            self.zip(o, |a, b| a * b)
+        }
+
+        #[inline(always)]
+        pub(crate) fn div(self, o: Self) -> Self {
+            self.zip(o, |a, b| a / b)
+        }
+
+        #[inline(always)]
+        pub(crate) fn sqrt(self) -> Self {
+            self.map(f32::sqrt)
+        }
+
+        #[inline(always)]
+        pub(crate) fn min(self, o: Self) -> Self {
+            self.zip(o, |a, b| if a < b { a } else { b })
+        }
+
+        #[inline(always)]
+        pub(crate) fn max(self, o: Self) -> Self {
+            self.zip(o, |a, b| if a > b { a } else { b })
+        }
+
+        #[inline(always)]
+        pub(crate) fn lt(self, o: Self) -> Self {
+            self.zip(o, |a, b| Self::mask(a < b))
+        }
+
+        #[inline(always)]
+        pub(crate) fn le(self, o: Self) -> Self {
+            self.zip(o, |a, b| Self::mask(a <= b))
+        }
+
+        #[inline(always)]
+        pub(crate) fn and(self, o: Self) -> Self {
+            self.zip(o, |a, b| f32::from_bits(a.to_bits() & b.to_bits()))
+        }
+
+        #[inline(always)]
+        pub(crate) fn or(self, o: Self) -> Self {
+            self.zip(o, |a, b| f32::from_bits(a.to_bits() | b.to_bits()))
+        }
+
+        #[inline(always)]
+        pub(crate) fn select(self, a: Self, b: Self) -> Self {
+            let [m0, m1, m2, m3] = self.0;
+            let pick = |m: f32, a: f32, b: f32| if m.to_bits() != 0 { a } else { b };
+            Self([
+                pick(m0, a.0[0], b.0[0]),
+                pick(m1, a.0[1], b.0[1]),
+                pick(m2, a.0[2], b.0[2]),
+                pick(m3, a.0[3], b.0[3]),
+            ])
+        }
+
+        #[inline(always)]
+        pub(crate) fn any(self) -> bool {
+            self.0.iter().any(|m| m.to_bits() != 0)
+        }
+
+        #[inline(always)]
+        pub(crate) fn pow2_from_magic(self) -> Self {
+            self.map(|m| f32::from_bits(m.to_bits().wrapping_add(super::POW2_BIAS as u32) << 23))
+        }
+    }
+}
+
+pub(crate) use imp::F32x4;
+
+// 1.5 * 2^23: 더하면 정수 부분이 가수 아래 비트로 반올림되어 들어간다
+const MAGIC: f32 = 12_582_912.0;
+// 위 값의 비트(0x4b40_0000)를 빼고 지수 바이어스 127을 더한다
+const POW2_BIAS: i32 = 127 - 0x4b40_0000;
+
+impl F32x4 {
+    // 마지막 몇 칸만 남은 경우: 모자란 칸은 0으로 채운다
+    #[inline(always)]
+    pub(crate) fn load_partial(src: &[f32]) -> Self {
+        let mut a = [0.0; 4];
+        a[..src.len()].copy_from_slice(src);
+        Self::from_array(a)
+    }
+
+    #[inline(always)]
+    pub(crate) fn store_partial(self, dst: &mut [f32]) {
+        let n = dst.len();
+        dst.copy_from_slice(&self.to_array()[..n]);
+    }
+
+    // 앞에서 n칸(LANES 이하)만 읽고 쓴다
+    #[inline(always)]
+    pub(crate) fn load_n(src: &[f32], n: usize) -> Self {
+        if n == LANES {
+            Self::load(src)
+        } else {
+            Self::load_partial(&src[..n])
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn store_n(self, dst: &mut [f32], n: usize) {
+        if n == LANES {
+            self.store(dst)
+        } else {
+            self.store_partial(&mut dst[..n])
+        }
+    }
+
+    // 0 - x와 큰 쪽을 고르므로 -0도 +0이 된다
+    #[inline(always)]
+    pub(crate) fn abs(self) -> Self {
+        self.max(Self::splat(0.0).sub(self))
+    }
+
+    // e^x (Cephes expf와 같은 다항식, 상대 오차 약 2e-7). 칸마다 같은 값을 내도록 반올림도 덧셈으로만 한다.
+    #[inline(always)]
+    pub(crate) fn exp(self) -> Self {
+        let x = self.max(Self::splat(-87.0)).min(Self::splat(88.0));
+        let magic = x
+            .mul(Self::splat(std::f32::consts::LOG2_E))
+            .add(Self::splat(MAGIC));
+        let n = magic.sub(Self::splat(MAGIC));
+        // ln 2 = C1 + C2 (C1은 가수가 짧아 n * C1이 정확하다)
+        let r = x
+            .sub(n.mul(Self::splat(0.693_359_4)))
+            .sub(n.mul(Self::splat(-2.121_944_4e-4)));
+        let mut p = Self::splat(1.987_569_1e-4);
+        for c in [
+            1.398_199_9e-3,
+            8.333_452e-3,
+            4.166_579_6e-2,
+            1.666_666_6e-1,
+            0.5,
+        ] {
+            p = p.mul(r).add(Self::splat(c));
+        }
+        let y = p.mul(r.mul(r)).add(r).add(Self::splat(1.0));
+        y.mul(magic.pow2_from_magic())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exp_matches_std() {
+        let mut x = -80.0f32;
+        while x < 80.0 {
+            let got = F32x4::from_array([x, x + 0.1, x + 0.2, x + 0.3]).exp();
+            for (k, g) in got.to_array().into_iter().enumerate() {
+                let want = (x + 0.1 * k as f32).exp();
+                assert!(
+                    (g - want).abs() <= want * 1e-6,
+                    "exp({}) = {} != {}",
+                    x,
+                    g,
+                    want
+                );
+            }
+            x += 0.37;
+        }
+    }
+}
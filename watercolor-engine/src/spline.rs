@@ -0,0 +1,190 @@
+// 표본 열 (x, y, 압력, 시각)을 Catmull-Rom 곡선으로 잇고 호 길이 간격으로 붓 자국 위치를 뽑는다.
+// 구심(centripetal) 매개화라 표본 간격이 고르지 않아도 곡선이 꼬이거나 튀어나가지 않는다.
+
+// 표본 하나의 값 수: x, y, 압력, 시각(ms)
+pub(crate) const SAMPLE: usize = 4;
+// 곡선 길이를 잴 때 한 구간을 이 간격(셀)보다 잘게 나눈다
+const FINE: f32 = 0.25;
+// 이보다 가까운 표본은 하나로 본다
+const MIN_GAP: f32 = 1e-3;
+
+#[derive(Clone, Copy)]
+struct Point {
+    x: f32,
+    y: f32,
+    pressure: f32,
+    time: f32,
+}
+
+pub(crate) struct Dab {
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+    // 이 자국이 놓인 구간의 이동 속도 (셀/초)
+    pub velocity: f32,
+    // 곡선 접선 방향 (라디안)
+    pub angle: f32,
+}
+
+// 끝의 남는 값은 버린다
+fn points(samples: &[f32]) -> Vec<Point> {
+    let mut out: Vec<Point> = Vec::new();
+    for s in samples.chunks_exact(SAMPLE) {
+        let p = Point {
+            x: s[0],
+            y: s[1],
+            pressure: s[2],
+            time: s[3],
+        };
+        match out.last_mut() {
+            Some(last) if dist(last, &p) < MIN_GAP => *last = p,
+            _ => out.push(p),
+        }
+    }
+    out
+}
+
+fn dist(a: &Point, b: &Point) -> f32 {
+    ((b.x - a.x) * (b.x - a.x) + (b.y - a.y) * (b.y - a.y)).sqrt()
+}
+
+// a에서 b 쪽으로 t만큼 (t는 0..1 밖이어도 된다)
+fn mix(a: (f32, f32), b: (f32, f32), t: f32) -> (f32, f32) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+// p1..p2 구간 위의 점 (u는 0..1). Barry-Goldman 피라미드.
+fn centripetal(p: [(f32, f32); 4], u: f32) -> (f32, f32) {
+    let knot = |a: (f32, f32), b: (f32, f32)| {
+        ((b.0 - a.0) * (b.0 - a.0) + (b.1 - a.1) * (b.1 - a.1))
+            .sqrt()
+            .sqrt()
+            .max(MIN_GAP)
+    };
+    let t0 = 0.0;
+    let t1 = t0 + knot(p[0], p[1]);
+    let t2 = t1 + knot(p[1], p[2]);
+    let t3 = t2 + knot(p[2], p[3]);
+    let t = t1 + (t2 - t1) * u;
+    let a1 = mix(p[0], p[1], (t - t0) / (t1 - t0));
+    let a2 = mix(p[1], p[2], (t - t1) / (t2 - t1));
+    let a3 = mix(p[2], p[3], (t - t2) / (t3 - t2));
+    let b1 = mix(a1, a2, (t - t0) / (t2 - t0));
+    let b2 = mix(a2, a3, (t - t1) / (t3 - t1));
+    mix(b1, b2, (t - t1) / (t2 - t1))
+}
+
+// spacing 셀마다 붓 자국 하나. 첫 표본에서 시작하고 마지막 표본에서 끝난다.
+pub(crate) fn dabs(samples: &[f32], spacing: f32) -> Vec<Dab> {
+    let pts = points(samples);
+    let n = pts.len();
+    let Some(&first) = pts.first() else {
+        return Vec::new();
+    };
+    if n == 1 {
+        return vec![Dab {
+            x: first.x,
+            y: first.y,
+            pressure: first.pressure,
+            velocity: 0.0,
+            angle: 0.0,
+        }];
+    }
+    let xy = |i: usize| (pts[i].x, pts[i].y);
+    // 양 끝은 바깥으로 한 칸 뻗은 가상의 점을 쓴다
+    let control = |i: isize| {
+        if i < 0 {
+            mix(xy(1), xy(0), 2.0)
+        } else if i as usize >= n {
+            mix(xy(n - 2), xy(n - 1), 2.0)
+        } else {
+            xy(i as usize)
+        }
+    };
+
+    let mut out = Vec::new();
+    // 마지막 자국 뒤로 곡선을 따라 온 거리
+    let mut travelled = spacing;
+    for i in 0..n - 1 {
+        let (a, b) = (pts[i], pts[i + 1]);
+        let ctrl = [
+            control(i as isize - 1),
+            xy(i),
+            xy(i + 1),
+            control(i as isize + 2),
+        ];
+        let dt = (b.time - a.time) / 1000.0;
+        let velocity = if dt > 0.0 { dist(&a, &b) / dt } else { 0.0 };
+        let pieces = (dist(&a, &b) / FINE).ceil().max(1.0) as usize;
+        let mut prev = xy(i);
+        for k in 1..=pieces {
+            let u = k as f32 / pieces as f32;
+            let cur = centripetal(ctrl, u);
+            let (dx, dy) = (cur.0 - prev.0, cur.1 - prev.1);
+            let len = (dx * dx + dy * dy).sqrt();
+            let angle = dy.atan2(dx);
+            // 이 조각 안에서 spacing마다 자국을 놓는다 (첫 자국은 곡선 시작점)
+            let mut at = spacing - travelled;
+            while at <= len {
+                let f = if len > 0.0 { at / len } else { 0.0 };
+                let uf = u - (1.0 - f) / pieces as f32;
+                out.push(Dab {
+                    x: prev.0 + dx * f,
+                    y: prev.1 + dy * f,
+                    pressure: a.pressure + (b.pressure - a.pressure) * uf,
+                    velocity,
+                    angle,
+                });
+                at += spacing;
+            }
+            travelled = len - (at - spacing);
+            prev = cur;
+        }
+    }
+    // 끝점에도 자국을 남긴다
+    let last = pts[n - 1];
+    if travelled > MIN_GAP {
+        let angle = out.last().map_or(0.0, |d| d.angle);
+        let velocity = out.last().map_or(0.0, |d| d.velocity);
+        out.push(Dab {
+            x: last.x,
+            y: last.y,
+            pressure: last.pressure,
+            velocity,
+            angle,
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dabs_are_evenly_spaced_between_endpoints() {
+        // 직선 위 고르지 않은 표본
+        let samples = [
+            0.0, 0.0, 1.0, 0.0, 3.0, 0.0, 1.0, 10.0, 17.0, 0.0, 1.0, 20.0, 30.0, 0.0, 1.0, 30.0,
+        ];
+        let spacing = 2.0;
+        let d = dabs(&samples, spacing);
+        let first = &d[0];
+        let last = &d[d.len() - 1];
+        assert_eq!((first.x, first.y), (0.0, 0.0));
+        assert_eq!((last.x, last.y), (30.0, 0.0));
+        for w in d[..d.len() - 1].windows(2) {
+            let gap = ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt();
+            assert!((gap - spacing).abs() < 0.01, "gap {}", gap);
+        }
+        let tail =
+            ((last.x - d[d.len() - 2].x).powi(2) + (last.y - d[d.len() - 2].y).powi(2)).sqrt();
+        assert!(tail > 0.0 && tail <= spacing);
+    }
+
+    #[test]
+    fn single_sample_gives_one_dab() {
+        assert_eq!(dabs(&[5.0, 6.0, 0.5, 0.0], 1.0).len(), 1);
+        assert!(dabs(&[], 1.0).is_empty());
+    }
+}
@@ -0,0 +1,528 @@
+// 엔진 상태 저장/불러오기 (프로젝트 파일)
+// 헤더 뒤에 태그가 붙은 청크를 이어 붙인다. 모르는 청크는 건너뛰고, 없는 청크나
+// 청크 끝에 새로 추가된 필드는 기본값으로 채워서 구버전 파일도 계속 읽을 수 있다.
+// v2: BRSH, TIPS 청크와 PHYS 끝 필드(실루엣, 번짐, 배율, 마른 붓)를 더했다. v1 파일에는 없으므로 기본값이 된다.
+//
+// 헤더: "WCEN" | 버전 u16 | 예약 u16 | width u32 | height u32
+// 청크: 태그 [u8; 4] | 길이 u32 | 내용   (모든 숫자는 little-endian)
+
+use crate::history::History;
+use crate::km::KsCoeffs;
+use crate::pigment::{Pigment, PigmentLibrary};
+use crate::tip::{self, Tip};
+use crate::{WatercolorEngine, HISTORY_BYTES, MAX_PIGMENTS, MAX_REPLAY_SCALE};
+
+const MAGIC: &[u8; 4] = b"WCEN";
+pub(crate) const FORMAT_VERSION: u16 = 2;
+
+// 손상된 헤더로 거대한 버퍼를 잡지 않도록 캔버스 크기를 제한한다
+pub const MAX_CELLS: u64 = 8192 * 8192;
+// step이 도는 압력 반복 횟수와 배율 (배율 재생을 두 번 거친 상태까지)
+const MAX_ITERATIONS: u32 = 1000;
+const MAX_SCALE: f32 = (MAX_REPLAY_SCALE * MAX_REPLAY_SCALE) as f32;
+
+const TAG_PHYSICS: &[u8; 4] = b"PHYS";
+const TAG_FLUID: &[u8; 4] = b"FLUD";
+const TAG_CAPILLARY: &[u8; 4] = b"CAPL";
+const TAG_WET_AREA: &[u8; 4] = b"WETA";
+const TAG_PAPER: &[u8; 4] = b"PAPR";
+const TAG_PIGMENTS: &[u8; 4] = b"PIGL";
+const TAG_PALETTE: &[u8; 4] = b"PALT";
+const TAG_PIXELS: &[u8; 4] = b"PIXL";
+const TAG_BRUSH: &[u8; 4] = b"BRSH";
+const TAG_TIP: &[u8; 4] = b"TIPS";
+
+pub(crate) struct Writer {
+    pub(crate) buf: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+    pub(crate) fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub(crate) fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub(crate) fn f32(&mut self, v: f32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    pub(crate) fn floats(&mut self, data: &[f32]) {
+        self.u32(data.len() as u32);
+        for &v in data {
+            self.f32(v);
+        }
+    }
+    pub(crate) fn bytes(&mut self, data: &[u8]) {
+        self.u32(data.len() as u32);
+        self.buf.extend_from_slice(data);
+    }
+
+    pub(crate) fn chunk(&mut self, tag: &[u8; 4], body: impl FnOnce(&mut Writer)) {
+        self.buf.extend_from_slice(tag);
+        let len_at = self.buf.len();
+        self.u32(0);
+        body(self);
+        let len = (self.buf.len() - len_at - 4) as u32;
+        self.buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < n {
+            return Err("상태 데이터가 중간에 끊겼습니다".to_string());
+        }
+        let out = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(out)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    pub(crate) fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    pub(crate) fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    pub(crate) fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+    pub(crate) fn floats(&mut self, expected: usize) -> Result<Vec<f32>, String> {
+        let len = self.u32()? as usize;
+        if len != expected {
+            return Err(format!(
+                "필드 크기가 맞지 않습니다 ({} != {})",
+                len, expected
+            ));
+        }
+        let raw = self.take(len * 4)?;
+        Ok(raw
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    // 청크 끝에 나중에 추가된 필드: 구버전 파일에는 없으므로 기본값을 쓴다
+    pub(crate) fn f32_or(&mut self, default: f32) -> Result<f32, String> {
+        if self.remaining() == 0 {
+            return Ok(default);
+        }
+        self.f32()
+    }
+    pub(crate) fn u32_or(&mut self, default: u32) -> Result<u32, String> {
+        if self.remaining() == 0 {
+            return Ok(default);
+        }
+        self.u32()
+    }
+    pub(crate) fn u8_or(&mut self, default: u8) -> Result<u8, String> {
+        if self.remaining() == 0 {
+            return Ok(default);
+        }
+        self.u8()
+    }
+}
+
+pub(crate) fn write_pigment(w: &mut Writer, pig: &Pigment) {
+    w.bytes(pig.name.as_bytes());
+    for c in 0..3 {
+        w.f32(pig.ks.k[c]);
+    }
+    for c in 0..3 {
+        w.f32(pig.ks.s[c]);
+    }
+    w.f32(pig.density);
+    w.f32(pig.staining);
+    w.f32(pig.granulation);
+    w.f32(pig.flocculation);
+}
+
+pub(crate) fn read_pigment(r: &mut Reader) -> Result<Pigment, String> {
+    let name = String::from_utf8(r.bytes()?.to_vec())
+        .map_err(|_| "안료 이름이 UTF-8이 아닙니다".to_string())?;
+    let mut ks = KsCoeffs::ZERO;
+    for c in 0..3 {
+        ks.k[c] = r.f32()?;
+    }
+    for c in 0..3 {
+        ks.s[c] = r.f32()?;
+    }
+    Ok(Pigment {
+        name,
+        ks,
+        density: r.f32()?,
+        staining: r.f32()?,
+        granulation: r.f32()?,
+        flocculation: r.f32()?,
+    })
+}
+
+pub(crate) fn save(e: &WatercolorEngine) -> Vec<u8> {
+    let mut w = Writer { buf: Vec::new() };
+    w.buf.extend_from_slice(MAGIC);
+    w.u16(FORMAT_VERSION);
+    w.u16(0);
+    w.u32(e.width as u32);
+    w.u32(e.height as u32);
+
+    w.chunk(TAG_PHYSICS, |w| {
+        w.f32(e.dt);
+        w.f32(e.evaporation);
+        w.f32(e.viscosity);
+        w.f32(e.pressure);
+        w.u32(e.iterations);
+        w.f32(e.adhesion);
+        w.f32(e.granularity);
+        w.f32(e.absorption);
+        w.f32(e.capillary_diffusion);
+        w.f32(e.bloom_strength);
+        w.f32(e.edge_darkening);
+        w.f32(e.divergence_residual);
+        w.u8(e.show_texture as u8);
+        w.u32(e.debug_view);
+        w.f32(e.silhouette_strength);
+        w.f32(e.edge_bleed_strength);
+        w.f32(e.scale);
+        w.u8(e.drybrush as u8);
+    });
+    w.chunk(TAG_FLUID, |w| {
+        w.floats(&e.h);
+        w.floats(&e.u);
+        w.floats(&e.v);
+        w.floats(&e.p);
+        w.floats(&e.mask);
+    });
+    w.chunk(TAG_CAPILLARY, |w| {
+        w.floats(&e.s);
+        w.floats(&e.capacity);
+    });
+    w.chunk(TAG_WET_AREA, |w| {
+        w.floats(&e.wet_mask);
+        w.floats(&e.edge_dist);
+    });
+    w.chunk(TAG_PAPER, |w| {
+        w.floats(&e.paper_h);
+        w.floats(&e.paper_render);
+        w.floats(&e.silhouette_map);
+        w.floats(&e.silhouette_edge);
+    });
+    w.chunk(TAG_PIGMENTS, |w| {
+        w.u32(e.pigments.len() as u32);
+        for pig in e.pigments.iter() {
+            write_pigment(w, pig);
+        }
+    });
+    w.chunk(TAG_PALETTE, |w| {
+        w.u32(e.palette.len() as u32);
+        for (slot, &id) in e.palette.iter().enumerate() {
+            w.u32(id);
+            w.floats(&e.g[slot]);
+            w.floats(&e.d[slot]);
+        }
+    });
+    w.chunk(TAG_BRUSH, |w| {
+        w.f32(e.brush.water);
+        w.u32(e.brush.pigment.len() as u32);
+        for &(id, amount) in &e.brush.pigment {
+            w.u32(id);
+            w.f32(amount);
+        }
+    });
+    // 프리셋은 번호만, 불러온 이미지는 닿는 정도까지 적는다
+    w.chunk(TAG_TIP, |w| match &e.tip {
+        Some(t) if t.kind == tip::CUSTOM => {
+            w.u32(t.kind);
+            w.u32(t.width as u32);
+            w.u32(t.height as u32);
+            w.floats(&t.alpha);
+        }
+        Some(t) => w.u32(t.kind),
+        None => w.u32(tip::ROUND),
+    });
+    w.chunk(TAG_PIXELS, |w| w.bytes(&e.pixels));
+    w.buf
+}
+
+// 값이 망가진 PHYS 청크로 step이 멈추거나 NaN을 퍼뜨리지 않도록 한다
+fn check_physics(e: &WatercolorEngine) -> Result<(), String> {
+    let floats = [
+        e.dt,
+        e.evaporation,
+        e.viscosity,
+        e.pressure,
+        e.adhesion,
+        e.granularity,
+        e.absorption,
+        e.capillary_diffusion,
+        e.bloom_strength,
+        e.edge_darkening,
+        e.divergence_residual,
+        e.silhouette_strength,
+        e.edge_bleed_strength,
+        e.scale,
+    ];
+    if floats.iter().any(|v| !v.is_finite()) {
+        return Err("물리 값에 유한하지 않은 수가 있습니다".to_string());
+    }
+    if e.iterations > MAX_ITERATIONS {
+        return Err(format!("반복 횟수가 너무 큽니다 ({})", e.iterations));
+    }
+    if e.scale < 1.0 || e.scale > MAX_SCALE || e.scale.fract() != 0.0 {
+        return Err(format!("배율이 올바르지 않습니다 ({})", e.scale));
+    }
+    Ok(())
+}
+
+// 필드 청크가 모두 있고 캔버스 크기만큼의 값을 담고 있는지 엔진을 만들기 전에 확인한다.
+// 헤더만 있는 파일로 거대한 캔버스를 잡지 않도록 한다.
+// 처음 형식부터 있던 청크는 모두 요구하므로 청크 경계에서 잘린 파일도 걸러진다 (PIXL이 맨 끝이다).
+fn check_fields(r: &Reader, w: usize, h: usize) -> Result<(), String> {
+    let total = w * h;
+    let field = |n: usize| 4 + n * 4;
+    let required: [(&[u8; 4], usize); 8] = [
+        (TAG_PHYSICS, 0),
+        (
+            TAG_FLUID,
+            field(total) * 3 + field((w + 1) * h) + field(w * (h + 1)),
+        ),
+        (TAG_CAPILLARY, field(total) * 2),
+        (TAG_WET_AREA, field(total) * 2),
+        (TAG_PAPER, field(total) * 4),
+        (TAG_PIGMENTS, 4),
+        (TAG_PALETTE, 4),
+        (TAG_PIXELS, 4),
+    ];
+    let mut found = [false; 8];
+    let mut scan = r.clone();
+    while scan.remaining() > 0 {
+        let tag = scan.take(4)?;
+        let len = scan.bytes()?.len();
+        for (k, (want, size)) in required.iter().enumerate() {
+            if tag == *want {
+                if len < *size {
+                    return Err(format!(
+                        "{} 청크가 캔버스 크기보다 짧습니다",
+                        String::from_utf8_lossy(tag)
+                    ));
+                }
+                found[k] = true;
+            }
+        }
+    }
+    match found.iter().position(|&f| !f) {
+        Some(k) => Err(format!(
+            "{} 청크가 없습니다",
+            String::from_utf8_lossy(required[k].0)
+        )),
+        None => Ok(()),
+    }
+}
+
+// 실패하면 엔진은 그대로 두고 이유를 돌려준다.
+pub(crate) fn load(data: &[u8]) -> Result<WatercolorEngine, String> {
+    let mut r = Reader::new(data);
+    if r.take(4)? != MAGIC {
+        return Err("수채화 엔진 상태 파일이 아닙니다".to_string());
+    }
+    let version = r.u16()?;
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "더 새로운 버전의 상태 파일입니다 (v{} > v{})",
+            version, FORMAT_VERSION
+        ));
+    }
+    r.u16()?;
+    let width = r.u32()?;
+    let height = r.u32()?;
+    if width == 0 || height == 0 || width as u64 * height as u64 > MAX_CELLS {
+        return Err(format!(
+            "캔버스 크기가 올바르지 않습니다 ({}x{})",
+            width, height
+        ));
+    }
+
+    check_fields(&r, width as usize, height as usize)?;
+
+    let mut e = WatercolorEngine::new(width, height);
+    let total = e.total;
+    while r.remaining() > 0 {
+        let tag = r.take(4)?;
+        let body = r.bytes()?;
+        let mut c = Reader::new(body);
+        match tag {
+            t if t == TAG_PHYSICS => {
+                e.dt = c.f32_or(e.dt)?;
+                e.evaporation = c.f32_or(e.evaporation)?;
+                e.viscosity = c.f32_or(e.viscosity)?;
+                e.pressure = c.f32_or(e.pressure)?;
+                e.iterations = c.u32_or(e.iterations)?;
+                e.adhesion = c.f32_or(e.adhesion)?;
+                e.granularity = c.f32_or(e.granularity)?;
+                e.absorption = c.f32_or(e.absorption)?;
+                e.capillary_diffusion = c.f32_or(e.capillary_diffusion)?;
+                e.bloom_strength = c.f32_or(e.bloom_strength)?;
+                e.edge_darkening = c.f32_or(e.edge_darkening)?;
+                e.divergence_residual = c.f32_or(e.divergence_residual)?;
+                e.show_texture = c.u8_or(e.show_texture as u8)? != 0;
+                e.debug_view = c.u32_or(e.debug_view)?;
+                e.silhouette_strength = c.f32_or(e.silhouette_strength)?;
+                e.edge_bleed_strength = c.f32_or(e.edge_bleed_strength)?;
+                e.scale = c.f32_or(e.scale)?;
+                e.drybrush = c.u8_or(e.drybrush as u8)? != 0;
+            }
+            t if t == TAG_FLUID => {
+                e.h = c.floats(total)?;
+                e.u = c.floats((e.width + 1) * e.height)?;
+                e.v = c.floats(e.width * (e.height + 1))?;
+                e.p = c.floats(total)?;
+                e.mask = c.floats(total)?;
+            }
+            t if t == TAG_CAPILLARY => {
+                e.s = c.floats(total)?;
+                e.capacity = c.floats(total)?;
+            }
+            t if t == TAG_WET_AREA => {
+                e.wet_mask = c.floats(total)?;
+                e.edge_dist = c.floats(total)?;
+            }
+            t if t == TAG_PAPER => {
+                e.paper_h = c.floats(total)?;
+                e.paper_render = c.floats(total)?;
+                e.silhouette_map = c.floats(total)?;
+                e.silhouette_edge = c.floats(total)?;
+            }
+            t if t == TAG_PIGMENTS => {
+                let count = c.u32()?;
+                let mut pigments = Vec::new();
+                for _ in 0..count {
+                    pigments.push(read_pigment(&mut c)?);
+                }
+                e.pigments = PigmentLibrary::from_pigments(pigments);
+            }
+            t if t == TAG_PALETTE => {
+                let count = c.u32()? as usize;
+                if count > MAX_PIGMENTS {
+                    return Err(format!("팔레트 슬롯이 너무 많습니다 ({})", count));
+                }
+                e.palette.clear();
+                e.g.clear();
+                e.d.clear();
+                for _ in 0..count {
+                    e.palette.push(c.u32()?);
+                    e.g.push(c.floats(total)?);
+                    e.d.push(c.floats(total)?);
+                }
+            }
+            t if t == TAG_BRUSH => {
+                e.brush.water = c.f32()?;
+                let count = c.u32()?;
+                e.brush.pigment.clear();
+                for _ in 0..count {
+                    let id = c.u32()?;
+                    e.brush.pigment.push((id, c.f32()?));
+                }
+            }
+            t if t == TAG_TIP => {
+                let kind = c.u32()?;
+                e.tip = if kind == tip::CUSTOM {
+                    let width = c.u32()?;
+                    let height = c.u32()?;
+                    if width == 0 || height == 0 || width > tip::MAX_SIDE || height > tip::MAX_SIDE
+                    {
+                        return Err(format!(
+                            "붓끝 크기가 올바르지 않습니다 ({}x{})",
+                            width, height
+                        ));
+                    }
+                    let alpha = c.floats(width as usize * height as usize)?;
+                    Some(Tip {
+                        kind,
+                        width: width as usize,
+                        height: height as usize,
+                        alpha,
+                    })
+                } else {
+                    Tip::preset(kind)
+                };
+            }
+            t if t == TAG_PIXELS => {
+                let pixels = c.bytes()?;
+                if pixels.len() == total * 4 {
+                    e.pixels = pixels.to_vec();
+                }
+            }
+            _ => {}
+        }
+    }
+    check_physics(&e)?;
+    if e.palette.iter().any(|&id| e.pigments.get(id).is_none()) {
+        return Err("팔레트가 없는 안료를 가리킵니다".to_string());
+    }
+    // 라이브러리에 없는 안료는 칠할 수 없어 붓에 영영 남으므로 털어 낸다
+    e.brush.pigment.retain(|&(id, _)| e.pigments.get(id).is_some());
+    e.history = History::new(e.width, e.height, HISTORY_BYTES);
+    Ok(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn painted() -> WatercolorEngine {
+        let mut e = WatercolorEngine::new(16, 16);
+        let blue = e.find_pigment("ultramarine") as u32;
+        e.apply_brush_stroke(2, 2, 12, 10, 3.0, 0.9, 0.8, blue, 0.0);
+        e.step();
+        e
+    }
+
+    #[test]
+    fn truncated_state_is_rejected() {
+        let data = save(&painted());
+        assert!(load(&data).is_ok());
+        for cut in 0..data.len() {
+            assert!(load(&data[..cut]).is_err(), "{}바이트에서 잘림", cut);
+        }
+    }
+
+    #[test]
+    fn bad_physics_is_rejected() {
+        let broken: [fn(&mut WatercolorEngine); 5] = [
+            |e| e.iterations = u32::MAX,
+            |e| e.scale = f32::NAN,
+            |e| e.scale = 1.5,
+            |e| e.scale = 0.0,
+            |e| e.dt = f32::INFINITY,
+        ];
+        for (k, set) in broken.iter().enumerate() {
+            let mut e = painted();
+            set(&mut e);
+            assert!(load(&save(&e)).is_err(), "{}번째 값", k);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// 타일(TILE x TILE 셀) 단위 활동 지도.
+// 물, 섬유 수분, 부유 안료, 압력, 속도가 모두 정확히 0인 타일은 step의 어느 패스를 돌려도 바뀌지 않으므로 재운다.
+// step마다 깨어 있는 타일과 그 둘레(물이 한 step에 닿을 수 있는 거리)만 돌리고,
+// 끝나면 돌린 타일을 다시 보고 깨우거나 재운다. 그래서 결과는 캔버스 전체를 도는 것과 같다.
+// 렌더도 같은 지도를 쓴다: 깨우거나 돌린 타일만 다시 칠하고, 다시 칠한 영역을 모아 둔다.
+// 되돌리기 기록도 깨우거나 돌린 타일만 체크포인트와 비교한다.
+
+use crate::simd::LANES;
+
+pub(crate) const TILE: usize = 32;
+// 이전 값 스냅샷은 돌리는 타일에서 이만큼 넓혀 복사한다 (패스는 돌리는 셀 바로 옆 값까지 읽는다)
+const MARGIN: usize = 2;
+
+pub(crate) struct Tiles {
+    width: usize,
+    height: usize,
+    cols: usize,
+    rows: usize,
+    awake: Vec<bool>,
+    // 이번 step에 돌리는 타일 (깨어 있는 타일 + 둘레)
+    covered: Vec<bool>,
+    // 타일 행마다 돌리는 셀 열 구간 [x0, x1), 이어진 타일은 하나로 합친다
+    spans: Vec<Vec<(usize, usize)>>,
+    // 마지막 렌더 뒤 값이 바뀌었을 수 있는 타일
+    dirty: Vec<bool>,
+    // take_changed() 이후 다시 칠한 셀 영역 (x0, y0, x1, y1)
+    changed: Option<(usize, usize, usize, usize)>,
+    // 마지막 take_edited() 뒤 필드 값이 바뀌었을 수 있는 타일
+    edited: Vec<bool>,
+}
+
+impl Tiles {
+    // 처음에는 모두 깨워 두고, 첫 step이 끝나면 빈 타일이 잠든다
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        let cols = width.div_ceil(TILE);
+        let rows = height.div_ceil(TILE);
+        let mut tiles = Tiles {
+            width,
+            height,
+            cols,
+            rows,
+            awake: vec![true; cols * rows],
+            covered: vec![true; cols * rows],
+            spans: Vec::new(),
+            dirty: vec![true; cols * rows],
+            changed: None,
+            edited: vec![true; cols * rows],
+        };
+        tiles.plan(0);
+        tiles
+    }
+
+    pub(crate) fn wake_all(&mut self) {
+        self.awake.iter_mut().for_each(|a| *a = true);
+        self.edited.iter_mut().for_each(|e| *e = true);
+        self.touch_all();
+    }
+
+    pub(crate) fn wake_tile(&mut self, tile: usize) {
+        self.awake[tile] = true;
+        self.dirty[tile] = true;
+        self.edited[tile] = true;
+    }
+
+    // 렌더 설정이 바뀌어 모든 셀을 다시 칠해야 할 때
+    pub(crate) fn touch_all(&mut self) {
+        self.dirty.iter_mut().for_each(|d| *d = true);
+    }
+
+    // 셀 범위 [x0, x1] x [y0, y1]에 걸친 타일을 깨운다 (캔버스 밖은 잘린다)
+    pub(crate) fn wake(&mut self, x0: isize, y0: isize, x1: isize, y1: isize) {
+        let (w, h) = (self.width as isize, self.height as isize);
+        if x1 < 0 || y1 < 0 || x0 >= w || y0 >= h {
+            return;
+        }
+        let tx0 = x0.max(0) as usize / TILE;
+        let ty0 = y0.max(0) as usize / TILE;
+        let tx1 = x1.min(w - 1) as usize / TILE;
+        let ty1 = y1.min(h - 1) as usize / TILE;
+        for ty in ty0..=ty1 {
+            for tx in tx0..=tx1 {
+                self.wake_tile(ty * self.cols + tx);
+            }
+        }
+    }
+
+    // 깨어 있는 타일에서 reach 셀 안에 드는 타일까지 이번 step에 돌린다
+    pub(crate) fn plan(&mut self, reach: usize) {
+        let r = reach.div_ceil(TILE) as isize;
+        let (cols, rows) = (self.cols as isize, self.rows as isize);
+        self.covered.iter_mut().for_each(|c| *c = false);
+        for ty in 0..rows {
+            for tx in 0..cols {
+                if !self.awake[(ty * cols + tx) as usize] {
+                    continue;
+                }
+                for ny in (ty - r).max(0)..=(ty + r).min(rows - 1) {
+                    for nx in (tx - r).max(0)..=(tx + r).min(cols - 1) {
+                        self.covered[(ny * cols + nx) as usize] = true;
+                        self.dirty[(ny * cols + nx) as usize] = true;
+                        self.edited[(ny * cols + nx) as usize] = true;
+                    }
+                }
+            }
+        }
+
+        self.spans = self.row_spans(&self.covered);
+    }
+
+    // 타일 행마다 on인 타일의 셀 열 구간 [x0, x1) (이어진 타일은 하나로 합친다)
+    fn row_spans(&self, on: &[bool]) -> Vec<Vec<(usize, usize)>> {
+        (0..self.rows)
+            .map(|ty| {
+                let mut row: Vec<(usize, usize)> = Vec::new();
+                for tx in (0..self.cols).filter(|&tx| on[ty * self.cols + tx]) {
+                    let (x0, x1) = (tx * TILE, ((tx + 1) * TILE).min(self.width));
+                    match row.last_mut() {
+                        Some(last) if last.1 == x0 => last.1 = x1,
+                        _ => row.push((x0, x1)),
+                    }
+                }
+                row
+            })
+            .collect()
+    }
+
+    // y행에서 돌리는 셀 열 구간
+    pub(crate) fn spans(&self, y: usize) -> &[(usize, usize)] {
+        &self.spans[y / TILE]
+    }
+
+    // y행에서 돌리는 셀의 열 번호 (왼쪽부터)
+    pub(crate) fn cols(&self, y: usize) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.spans(y).iter().flat_map(|&(x0, x1)| x0..x1)
+    }
+
+    // cols(y)를 LANES칸씩 묶은 (첫 열, 칸 수)
+    pub(crate) fn col_chunks(&self, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.spans(y)
+            .iter()
+            .flat_map(|&(x0, x1)| (x0..x1).step_by(LANES).map(move |x| (x, LANES.min(x1 - x))))
+    }
+
+    // y행에서 돌리는 셀 사이의 세로 경계면(캔버스 가장자리 제외)을 LANES개씩 묶은 (첫 경계면, 개수)
+    pub(crate) fn inner_face_chunks(&self, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let w = self.width;
+        self.spans(y).iter().flat_map(move |&(x0, x1)| {
+            let (f0, f1) = (x0.max(1), (x1 + 1).min(w));
+            (f0..f1).step_by(LANES).map(move |f| (f, LANES.min(f1 - f)))
+        })
+    }
+
+    // y행에서 돌리는 셀에 닿은 세로 경계면 번호 (캔버스 가장자리 포함)
+    pub(crate) fn faces(&self, y: usize) -> impl Iterator<Item = usize> + '_ {
+        self.spans(y).iter().flat_map(|&(x0, x1)| x0..=x1)
+    }
+
+    // 돌리는 타일을 MARGIN칸 넓힌 직사각형 (y0, y1, x0, x1). 한 행이 row_len칸인 rows행 배열 기준이다.
+    pub(crate) fn regions(
+        &self,
+        rows: usize,
+        row_len: usize,
+    ) -> impl Iterator<Item = (usize, usize, usize, usize)> + '_ {
+        self.spans.iter().enumerate().flat_map(move |(ty, spans)| {
+            let y0 = (ty * TILE).saturating_sub(MARGIN);
+            let y1 = ((ty + 1) * TILE + MARGIN).min(rows);
+            spans.iter().map(move |&(x0, x1)| {
+                (
+                    y0,
+                    y1,
+                    x0.saturating_sub(MARGIN),
+                    (x1 + MARGIN).min(row_len),
+                )
+            })
+        })
+    }
+
+    // src의 regions만 dst로 복사한다 (dst는 재사용 버퍼). 그 밖의 dst 값은 이전 내용이므로 읽으면 안 된다.
+    pub(crate) fn copy(&self, src: &[f32], row_len: usize, dst: &mut Vec<f32>) {
+        dst.resize(src.len(), 0.0);
+        for (y0, y1, x0, x1) in self.regions(src.len() / row_len, row_len) {
+            for y in y0..y1 {
+                let r = y * row_len;
+                dst[r + x0..r + x1].copy_from_slice(&src[r + x0..r + x1]);
+            }
+        }
+    }
+
+    // 이번 step에 돌린 타일의 셀 범위 (타일 번호, x0, y0, x1, y1)
+    pub(crate) fn covered(&self) -> Vec<(usize, usize, usize, usize, usize)> {
+        (0..self.covered.len())
+            .filter(|&t| self.covered[t])
+            .map(|t| {
+                let (tx, ty) = (t % self.cols, t / self.cols);
+                let x1 = ((tx + 1) * TILE).min(self.width);
+                let y1 = ((ty + 1) * TILE).min(self.height);
+                (t, tx * TILE, ty * TILE, x1, y1)
+            })
+            .collect()
+    }
+
+    // 다시 칠할 타일의 셀 열 구간을 타일 행마다 돌려주고 비운다. 그 영역은 changed에 더한다.
+    pub(crate) fn take_dirty(&mut self) -> Vec<Vec<(usize, usize)>> {
+        let spans = self.row_spans(&self.dirty);
+        for (ty, row) in spans.iter().enumerate() {
+            let (y0, y1) = (ty * TILE, ((ty + 1) * TILE).min(self.height));
+            for &(x0, x1) in row {
+                self.changed = Some(match self.changed {
+                    Some((a, b, c, d)) => (a.min(x0), b.min(y0), c.max(x1), d.max(y1)),
+                    None => (x0, y0, x1, y1),
+                });
+            }
+        }
+        self.dirty.iter_mut().for_each(|d| *d = false);
+        spans
+    }
+
+    // 값이 바뀌었을 수 있는 타일 번호를 돌려주고 비운다
+    pub(crate) fn take_edited(&mut self) -> Vec<usize> {
+        let out = (0..self.edited.len()).filter(|&t| self.edited[t]).collect();
+        self.edited.iter_mut().for_each(|e| *e = false);
+        out
+    }
+
+    // 마지막으로 가져간 뒤 다시 칠한 영역 (x0, y0, x1, y1)
+    pub(crate) fn take_changed(&mut self) -> Option<(usize, usize, usize, usize)> {
+        self.changed.take()
+    }
+
+    pub(crate) fn set_awake(&mut self, tile: usize, awake: bool) {
+        self.awake[tile] = awake;
+    }
+
+    pub(crate) fn awake_count(&self) -> usize {
+        self.awake.iter().filter(|&&a| a).count()
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.awake.len()
+    }
+}
@@ -0,0 +1,138 @@
+// 붓끝 모양 (set_brush_tip / load_brush_tip).
+// 붓 자국의 닿는 정도를 타원 가우시안 대신 회색조 이미지에서 읽는다.
+// 이미지 가로가 획 진행 방향, 세로가 붓 폭 방향이고, 긴 변이 붓 지름(size * 2)에 맞게 늘어난다.
+
+use crate::smoothstep;
+
+// 프리셋 번호 (0은 붓끝 없음 = 기본 둥근 붓)
+pub(crate) const ROUND: u32 = 0;
+pub(crate) const FLAT: u32 = 1;
+pub(crate) const FILBERT: u32 = 2;
+pub(crate) const FAN: u32 = 3;
+pub(crate) const RIGGER: u32 = 4;
+pub(crate) const CUSTOM: u32 = 5;
+
+// 프리셋 이미지 크기
+const RES: usize = 64;
+// 손상된 입력으로 거대한 버퍼를 잡지 않도록 불러오는 이미지 크기를 제한한다
+pub(crate) const MAX_SIDE: u32 = 1024;
+// 부채붓 붓털 뭉치 수
+const FAN_CLUMPS: usize = 7;
+
+#[derive(Clone)]
+pub(crate) struct Tip {
+    pub kind: u32,
+    pub width: usize,
+    pub height: usize,
+    // 닿는 정도 0..1, 행 우선
+    pub alpha: Vec<f32>,
+}
+
+fn hash01(k: u32) -> f32 {
+    let h = k.wrapping_mul(2654435761) ^ (k >> 7).wrapping_mul(40503);
+    (h & 0xffff) as f32 / 65535.0
+}
+
+// 붓 폭 방향으로 붓털이 모인 곳과 성긴 곳의 결 (n은 폭 전체의 붓털 줄 수)
+fn streaks(v: f32, n: f32) -> f32 {
+    0.45 + 0.55 * hash01(((v + 1.0) * 0.5 * n) as u32)
+}
+
+impl Tip {
+    // 붓끝 없음(ROUND)이나 모르는 번호는 None
+    pub(crate) fn preset(kind: u32) -> Option<Tip> {
+        let shape: fn(f32, f32) -> f32 = match kind {
+            // 납작붓: 진행 방향으로 얇고 폭이 넓은 네모
+            FLAT => |u, v| {
+                (1.0 - smoothstep(0.14, 0.22, u.abs()))
+                    * (1.0 - smoothstep(0.84, 0.92, v.abs()))
+                    * streaks(v, 28.0)
+            },
+            // 필버트: 납작붓의 양 끝을 둥글린 타원
+            FILBERT => |u, v| {
+                let r = (u / 0.3) * (u / 0.3) + (v / 0.85) * (v / 0.85);
+                (1.0 - smoothstep(0.7, 1.0, r)) * streaks(v, 24.0)
+            },
+            // 부채붓: 호를 따라 벌어진 붓털 뭉치
+            FAN => |u, v| {
+                (0..FAN_CLUMPS)
+                    .map(|k| {
+                        let vk = -0.9 + 1.8 * k as f32 / (FAN_CLUMPS - 1) as f32;
+                        let uk = -0.15 + 0.3 * vk * vk;
+                        let across = (v - vk) / 0.08;
+                        (-across * across).exp()
+                            * (1.0 - smoothstep(0.08, 0.16, (u - uk).abs()))
+                            * (0.6 + 0.4 * hash01(k as u32 + 1))
+                    })
+                    .fold(0.0, f32::max)
+            },
+            // 리거: 진행 방향으로 길고 가는 붓
+            RIGGER => |u, v| {
+                let r = (u / 0.9) * (u / 0.9) + (v / 0.14) * (v / 0.14);
+                1.0 - smoothstep(0.6, 1.0, r)
+            },
+            _ => return None,
+        };
+        let mut alpha = Vec::with_capacity(RES * RES);
+        for y in 0..RES {
+            for x in 0..RES {
+                let u = (x as f32 + 0.5) / RES as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / RES as f32 * 2.0 - 1.0;
+                alpha.push(shape(u, v).clamp(0.0, 1.0));
+            }
+        }
+        Some(Tip {
+            kind,
+            width: RES,
+            height: RES,
+            alpha,
+        })
+    }
+
+    // RGBA 바이트 (load_paper_texture와 같은 형식). 어두울수록, 불투명할수록 붓털이 많이 닿는다.
+    pub(crate) fn from_rgba(data: &[u8], width: u32, height: u32) -> Option<Tip> {
+        if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+            return None;
+        }
+        let (w, h) = (width as usize, height as usize);
+        if data.len() < w * h * 4 {
+            return None;
+        }
+        let alpha = data[..w * h * 4]
+            .chunks_exact(4)
+            .map(|px| {
+                let gray = (px[0] as f32 * 0.3 + px[1] as f32 * 0.59 + px[2] as f32 * 0.11) / 255.0;
+                ((1.0 - gray) * (px[3] as f32 / 255.0)).clamp(0.0, 1.0)
+            })
+            .collect();
+        Some(Tip {
+            kind: CUSTOM,
+            width: w,
+            height: h,
+            alpha,
+        })
+    }
+
+    // 긴 변을 -1..1에 맞춘 붓 자국 좌표 (u: 진행 방향, v: 폭 방향)에서의 닿는 정도 (쌍선형)
+    pub(crate) fn sample(&self, u: f32, v: f32) -> f32 {
+        let side = self.width.max(self.height) as f32;
+        let x = (u * side + self.width as f32) * 0.5 - 0.5;
+        let y = (v * side + self.height as f32) * 0.5 - 0.5;
+        if x <= -1.0 || y <= -1.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return 0.0;
+        }
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let at = |xi: i32, yi: i32| {
+            if xi < 0 || yi < 0 || xi as usize >= self.width || yi as usize >= self.height {
+                0.0
+            } else {
+                self.alpha[yi as usize * self.width + xi as usize]
+            }
+        };
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = at(x0, y0) + (at(x0 + 1, y0) - at(x0, y0)) * fx;
+        let bottom = at(x0, y0 + 1) + (at(x0 + 1, y0 + 1) - at(x0, y0 + 1)) * fx;
+        top + (bottom - top) * fy
+    }
+}